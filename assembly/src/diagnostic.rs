@@ -0,0 +1,84 @@
+use std::fmt;
+
+// an error at a place in the source, shown like
+//
+// error: undefined symbol 'loop'
+//  --> main.bffasm:3:5
+//   |
+// 3 | jmp loop
+//   |     ^^^^
+//   = note: included from main.bffasm:2
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize, // 1-based like the debug info
+    pub column: usize, // 1-based, in characters
+    pub length: usize, // characters underlined, at least 1
+    pub source_line: String,
+    pub message: String,
+    pub included_from: Vec<(String, usize)>, // the `.include`s that lead to the file, innermost first
+    pub notes: Vec<String>, // shown under the source, i.e. where a label was first defined
+}
+
+impl Diagnostic {
+    // `start..end` are byte offsets into `source`, a span over several lines is cut off at the end of the first
+    pub fn new(file: &str, source: &str, start: usize, end: usize, message: &str) -> Diagnostic {
+        let start = start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |index| start + index);
+        let end = end.clamp(start, line_end);
+        Diagnostic {
+            file: file.to_string(),
+            line: source[..start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            length: source[start..end].chars().count().max(1),
+            source_line: source[line_start..line_end].trim_end_matches('\r').to_string(),
+            message: message.to_string(),
+            included_from: vec![],
+            notes: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        // tabs are kept so the carets line up with the source however wide the terminal draws them
+        let indent: String = self.source_line.chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{}--> {}:{}:{}", gutter, self.file, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{} | {}{}", gutter, indent, "^".repeat(self.length))?;
+        for (file, line) in &self.included_from {
+            write!(f, "\n{} = note: included from {}:{}", gutter, file, line)?;
+        }
+        for note in &self.notes {
+            write!(f, "\n{} = note: {}", gutter, note)?;
+        }
+        Ok(())
+    }
+}
+
+// every error found in a file, in source order
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, diagnostic) in self.diagnostics.iter().enumerate() {
+            if index > 0 {
+                write!(f, "\n\n")?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AsmError {}
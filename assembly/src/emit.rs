@@ -0,0 +1,71 @@
+use std::fmt::Write;
+use bffcore::engine::program::BFFProgram;
+
+// rust source for a `program()` function that builds the same BFFProgram, so a program can be embedded in a host
+// without reading a bfo file. debug info is left out
+pub fn to_rust(program: &BFFProgram) -> String {
+    let mut output = String::new();
+    writeln!(output, "use bffcore::constants::instructions::Instruction;").unwrap();
+    writeln!(output, "use bffcore::engine::program::BFFProgram;").unwrap();
+    if !program.symbols.is_empty() {
+        writeln!(output, "use bffcore::engine::section::{{Symbol, SymbolBinding, SymbolKind}};").unwrap();
+    }
+    if !program.relocations.is_empty() {
+        writeln!(output, "use bffcore::engine::section::Relocation;").unwrap();
+    }
+    writeln!(output).unwrap();
+    writeln!(output, "pub fn program() -> BFFProgram {{").unwrap();
+
+    let (major, minor, patch) = program.version;
+    writeln!(output, "    #[allow(unused_mut)]").unwrap();
+    writeln!(output, "    let mut program = BFFProgram::new(({}, {}, {}), vec![", major, minor, patch).unwrap();
+    for instruction in &program.instructions {
+        // tuple variants debug print as valid rust
+        writeln!(output, "        Instruction::{:?},", instruction).unwrap();
+    }
+    writeln!(output, "    ], vec![").unwrap();
+    for (address, string) in &program.string_table {
+        writeln!(output, "        ({}, {:?}.to_string()),", address, string).unwrap();
+    }
+    writeln!(output, "    ]);").unwrap();
+
+    if !program.data.is_empty() {
+        writeln!(output, "    program.data = vec![").unwrap();
+        for (address, bytes) in &program.data {
+            writeln!(output, "        ({}, vec!{:?}),", address, bytes).unwrap();
+        }
+        writeln!(output, "    ];").unwrap();
+    }
+    if !program.bss.is_empty() {
+        writeln!(output, "    program.bss = vec!{:?};", program.bss).unwrap();
+    }
+    if let Some(entry) = program.entry {
+        writeln!(output, "    program.entry = Some({});", entry).unwrap();
+    }
+    if !program.symbols.is_empty() {
+        writeln!(output, "    program.symbols = vec![").unwrap();
+        for symbol in &program.symbols {
+            writeln!(
+                output, "        Symbol::new({:?}, {}, SymbolKind::{:?}, SymbolBinding::{:?}),",
+                symbol.name, symbol.value, symbol.kind, symbol.binding
+            ).unwrap();
+        }
+        writeln!(output, "    ];").unwrap();
+    }
+    if program.compact {
+        writeln!(output, "    program.compact = true;").unwrap();
+    }
+    if program.object {
+        writeln!(output, "    program.object = true;").unwrap();
+    }
+    if !program.relocations.is_empty() {
+        writeln!(output, "    program.relocations = vec![").unwrap();
+        for relocation in &program.relocations {
+            writeln!(output, "        Relocation::new({}, {:?}),", relocation.index, relocation.symbol).unwrap();
+        }
+        writeln!(output, "    ];").unwrap();
+    }
+    writeln!(output, "    program").unwrap();
+    writeln!(output, "}}").unwrap();
+    output
+}
@@ -0,0 +1,102 @@
+#[macro_use]
+extern crate pest_derive;
+extern crate pest;
+
+pub mod parser;
+pub mod diagnostic;
+pub mod preprocessor;
+pub mod listing;
+pub mod emit;
+
+use std::path::PathBuf;
+use bffcore::constants::types::Address;
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::{Symbol, SymbolBinding, SymbolKind};
+use crate::diagnostic::AsmError;
+
+// what bffasm's command line can change, i.e. AssemblerConfig::new().define("SIZE", "16").object(true)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssemblerConfig {
+    pub object: bool,
+    pub defines: Vec<(String, String)>, // the last define of a name wins
+    pub include_dirs: Vec<PathBuf>,
+    pub strip_debug: bool, // leave the debug section out, so a bffdis round trip gives back the same bytes
+}
+
+impl AssemblerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn object(mut self, object: bool) -> Self {
+        self.object = object;
+        self
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn strip_debug(mut self, strip_debug: bool) -> Self {
+        self.strip_debug = strip_debug;
+        self
+    }
+
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+}
+
+pub fn assemble(source: &str) -> Result<BFFProgram, AsmError> {
+    assemble_file("<input>", source)
+}
+
+// like `assemble`, naming `file_name` in the debug info
+pub fn assemble_file(file_name: &str, source: &str) -> Result<BFFProgram, AsmError> {
+    assemble_with_config(file_name, source, &AssemblerConfig::new())
+}
+
+// a relocatable object for bffld, code labels and `.extern` symbols are left as relocations
+pub fn assemble_object(file_name: &str, source: &str) -> Result<BFFProgram, AsmError> {
+    assemble_with_config(file_name, source, &AssemblerConfig::new().object(true))
+}
+
+pub fn assemble_with_config(file_name: &str, source: &str, config: &AssemblerConfig) -> Result<BFFProgram, AsmError> {
+    let object = config.object;
+    let mut parser = parser::BffAsmParser::new();
+    parser.file_name = file_name.to_string();
+    parser.object = object;
+    parser.include_dirs = config.include_dirs.clone();
+    parser.defines = config.defines.clone();
+    parser.parse(source)?;
+
+    let binding = |name: &str| if parser.globals.iter().any(|global| global == name) { SymbolBinding::Global } else { SymbolBinding::Local };
+
+    // sorted so the same source always gives the same bytes
+    let mut symbols: Vec<Symbol> = parser.labels.iter()
+        .map(|(name, index)| Symbol::new(name, *index as Address, SymbolKind::Code, binding(name)))
+        .chain(parser.data_labels.iter().map(|(name, address)| Symbol::new(name, *address, SymbolKind::Data, binding(name))))
+        .collect();
+    symbols.sort_by(|a, b| (a.kind as u8, a.value, &a.name).cmp(&(b.kind as u8, b.value, &b.name)));
+    if object {
+        symbols.extend(parser.externs.iter().map(|name| Symbol::new(name, 0, SymbolKind::Code, SymbolBinding::Extern)));
+    }
+
+    let mut program = BFFProgram::new(parser.version, parser.instructions, parser.string_table);
+    program.compact = parser.compact;
+    program.entry = parser.entry;
+    program.data = parser.data;
+    program.bss = parser.bss;
+    program.symbols = symbols;
+    if !config.strip_debug && !parser.strip_debug {
+        program.debug = match parser.locations.is_empty() {
+            true => parser.debug_info,
+            false => parser.locations,
+        };
+    }
+    program.object = object;
+    program.relocations = parser.relocations;
+    Ok(program)
+}
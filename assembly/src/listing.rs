@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::{SymbolBinding, SymbolKind};
+
+// one line per instruction with its index, its encoded bytes (compact ones if the program is compact) and the
+// source line it came from, `sources` maps the file names in the debug info to their contents
+pub fn listing(program: &BFFProgram, sources: &HashMap<String, String>) -> String {
+    let mut labels: HashMap<usize, Vec<&str>> = HashMap::new();
+    for symbol in &program.symbols {
+        if symbol.kind == SymbolKind::Code && symbol.binding != SymbolBinding::Extern {
+            labels.entry(symbol.value as usize).or_default().push(&symbol.name);
+        }
+    }
+
+    let mut output = String::new();
+    for (index, instruction) in program.instructions.iter().enumerate() {
+        // labels and debug info are 1 based
+        let index = index + 1;
+        for label in labels.get(&index).into_iter().flatten() {
+            writeln!(output, "{:>7}{}:", "", label).unwrap();
+        }
+
+        let bytes = match program.compact {
+            true => instruction.to_compact_bytes(),
+            false => instruction.to_bfo_bytes().to_vec(),
+        };
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        write!(output, "{:>5}  {:<23}", index, bytes.join(" ")).unwrap();
+
+        // line 0 doesn't point at any source line, a `.loc` row can give it
+        if let Some(location) = program.debug.lookup(index) {
+            if let Some(line_index) = (location.line as usize).checked_sub(1) {
+                let line = sources.get(&location.file)
+                    .and_then(|source| source.lines().nth(line_index))
+                    .unwrap_or("");
+                write!(output, "  {}:{:<4} {}", location.file, location.line, line.trim()).unwrap();
+            }
+        }
+        output.truncate(output.trim_end().len());
+        output.push('\n');
+    }
+    output
+}
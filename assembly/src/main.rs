@@ -1,31 +1,165 @@
-mod parser;
+use std::collections::HashMap;
+use std::collections::vec_deque::VecDeque;
+use std::path::Path;
+use bffasm::AssemblerConfig;
+use bffcore::engine::program::BFFProgram;
 
-#[macro_use]
-extern crate pest_derive;
-extern crate pest;
+const USAGE: &str = "Usage: bffasm <file.bffasm>... [-o <out>] [-c] [--emit=bfo|json|rust] [--listing] [--strip-debug] [-D NAME=VALUE] [-I <dir>]";
 
-use std::fs;
-use bffcore::constants::constants::VERSION;
-use bffcore::engine::program::BFFProgram;
-use bffcore::engine::virtual_machine::VirtualMachine;
+#[derive(Copy, Clone, PartialEq)]
+enum Emit {
+    Bfo,
+    Json,
+    Rust,
+}
+
+fn main() {
+    let mut args: VecDeque<String> = std::env::args().collect();
+    let _path = args.pop_front().unwrap();
+    let mut inputs: Vec<String> = vec![];
+    let mut output_path: Option<String> = None;
+    let mut emit = Emit::Bfo;
+    let mut listing = false;
+    let mut config = AssemblerConfig::new();
+
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => output_path = Some(next_value(&mut args, &current_arg, "a file path")),
+            "-c" => config = config.object(true),
+            "--listing" => listing = true,
+            "--strip-debug" => config = config.strip_debug(true),
+            "--emit" => emit = parse_emit(&next_value(&mut args, "--emit", "bfo, json or rust")),
+            arg if arg.starts_with("--emit=") => emit = parse_emit(&arg["--emit=".len()..]),
+            "-D" => config = define(config, &next_value(&mut args, "-D", "NAME=VALUE")),
+            arg if arg.starts_with("-D") => config = define(config, &arg[2..]),
+            "-I" => config = config.include_dir(next_value(&mut args, "-I", "a directory")),
+            arg if arg.starts_with("-I") => config = config.include_dir(&arg[2..]),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            unknown if unknown.starts_with("-") && unknown != "-" => fail(&format!("Unknown Argument: '{}'\n{}", unknown, USAGE)),
+            input => inputs.push(input.to_string()),
+        }
+    }
+
+    if inputs.is_empty() {
+        let default = "./assembly/main.bffasm".to_string();
+        if !Path::new(&default).exists() {
+            fail(USAGE);
+        }
+        eprintln!("Warning: No input given, using '{}'", default);
+        inputs.push(default);
+    }
+    if inputs.len() > 1 && output_path.is_some() {
+        fail("'-o' can only be used with a single input file");
+    }
+    if listing && output_path.as_deref() == Some("-") {
+        fail("'--listing' can't be used with '-o -', the listing would be mixed into the output");
+    }
 
-fn main(){
-    let mut parser = parser::BffAsmParser::new();
-    let contents = &*fs::read_to_string("./assembly/main.bffasm").expect("Couldnt read file");
-    parser.parse(contents).expect("Couldnt parse file");
+    let mut failed = false;
+    for input in &inputs {
+        if let Err(e) = assemble(input, output_path.as_deref(), emit, listing, &config) {
+            eprintln!("Error: {}", e);
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
 
-    let mut minus = 8;
+// writes next to the input unless given an output, "-" is stdout
+fn assemble(input: &str, output_path: Option<&str>, emit: Emit, listing: bool, config: &AssemblerConfig) -> Result<(), String> {
+    let source = std::fs::read_to_string(input).map_err(|e| format!("Couldn't read '{}': {}", input, e))?;
+    let program = match bffasm::assemble_with_config(input, &source, config) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}\n", e);
+            let count = match e.diagnostics.len() {
+                1 => "an error".to_string(),
+                count => format!("{} errors", count),
+            };
+            return Err(format!("Couldn't assemble '{}' due to {}", input, count));
+        }
+    };
 
-    for (_addr, string) in &parser.string_table {
-        minus += string.len() + 8;
+    let output = match emit {
+        Emit::Bfo => program.to_bfo_bytes(),
+        Emit::Json => to_json(&program)?.into_bytes(),
+        Emit::Rust => bffasm::emit::to_rust(&program).into_bytes(),
+    };
+    let output_path = match output_path {
+        Some(output_path) => output_path.to_string(),
+        None => {
+            let extension = match emit {
+                Emit::Bfo if config.object => "o",
+                Emit::Bfo => "bfo",
+                Emit::Json => "json",
+                Emit::Rust => "rs",
+            };
+            Path::new(input).with_extension(extension).to_string_lossy().to_string()
+        }
+    };
+    if output_path == "-" {
+        use std::io::Write;
+        std::io::stdout().write_all(&output).map_err(|e| format!("Couldn't write to stdout: {}", e))?;
+    } else {
+        std::fs::write(&output_path, output).map_err(|e| format!("Couldn't write '{}': {}", output_path, e))?;
     }
 
-    let mut bfo_program = BFFProgram::new(VERSION, parser.instructions, parser.string_table);
+    if listing {
+        let mut sources = HashMap::new();
+        sources.insert(input.to_string(), source);
+        // and the files it includes
+        for (_, location) in &program.debug.locations {
+            if !sources.contains_key(&location.file) {
+                if let Ok(source) = std::fs::read_to_string(&location.file) {
+                    sources.insert(location.file.clone(), source);
+                }
+            }
+        }
+        print!("{}", bffasm::listing::listing(&program, &sources));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+fn to_json(program: &BFFProgram) -> Result<String, String> {
+    Ok(program.to_json())
+}
+
+#[cfg(not(feature = "json"))]
+fn to_json(_program: &BFFProgram) -> Result<String, String> {
+    Err("bffasm was built without the 'json' feature".to_string())
+}
+
+fn parse_emit(emit: &str) -> Emit {
+    match emit {
+        "bfo" => Emit::Bfo,
+        "json" => Emit::Json,
+        "rust" => Emit::Rust,
+        unknown => fail(&format!("'--emit' expects bfo, json or rust, got '{}'", unknown)),
+    }
+}
+
+// "NAME=VALUE", or just "NAME" which defines it as 1
+fn define(config: AssemblerConfig, define: &str) -> AssemblerConfig {
+    let (name, value) = define.split_once('=').unwrap_or((define, "1"));
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        fail(&format!("'-D' expects NAME=VALUE, got '{}'", define));
+    }
+    config.define(name, value)
+}
 
-    // write as bfo file
-    let compiled = bfo_program.to_bfo_bytes();
-    println!("Number of instructions: {:?}", compiled.len());
-    assert_eq!((compiled.len() - minus) % 8, 0, "Number of instructions is not a multiple of 8");
+fn next_value(args: &mut VecDeque<String>, flag: &str, expected: &str) -> String {
+    args.pop_front().unwrap_or_else(|| fail(&format!("'{}' expects {}", flag, expected)))
+}
 
-    fs::write("./assembly/main.bfo", compiled).expect("Couldnt write file");
-}
\ No newline at end of file
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
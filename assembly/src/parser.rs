@@ -1,14 +1,66 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use pest::Parser;
+use pest::error::{ErrorVariant, InputLocation};
+use pest::iterators::Pair;
 use bffcore::constants::instructions::Instruction;
-use bffcore::constants::types::Address;
+use bffcore::constants::constants::{REGISTER_COUNT, SECTIONED_VERSION, VERSION};
+use bffcore::constants::types::{Address, Bits, Byte};
+use bffcore::engine::debug_info::{DebugInfo, SourceLocation};
+use bffcore::engine::section::Relocation;
+use crate::diagnostic::{AsmError, Diagnostic};
+use crate::preprocessor::substitute_defines;
 
 
 pub enum ParseIntermediate {
     Instruction(Instruction),
-    Jump(String),
-    Jnz(u8, String),
-    Call(String),
+    Reference(Instruction, String, (usize, usize)), // the address operand is filled in with the symbol's value after parsing
+}
+
+// a file that was parsed, spans stored after its statement are offsets from `base` on
+struct Source {
+    file: String,
+    text: String,
+    base: usize,
+    included_from: Vec<(String, usize)>,
+}
+
+// a macro uses itself if it expands deeper than this
+const MAX_MACRO_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+    pub parameters: Vec<String>,
+    pub body: String,
+    pub file: String,
+    pub line: usize, // where the body starts in the source, for errors inside expansions
+}
+
+// why a statement was rejected, without a span the whole statement is underlined
+struct StatementError {
+    message: String,
+    span: Option<(usize, usize)>,
+    expanded: bool, // already names the macro it comes from
+    note: Option<String>,
+}
+
+impl StatementError {
+    fn at(span: (usize, usize), message: String) -> Self {
+        StatementError { message, span: Some(span), expanded: false, note: None }
+    }
+}
+
+impl From<String> for StatementError {
+    fn from(message: String) -> Self {
+        StatementError { message, span: None, expanded: false, note: None }
+    }
+}
+
+impl From<&str> for StatementError {
+    fn from(message: &str) -> Self {
+        StatementError::from(message.to_string())
+    }
 }
 
 
@@ -21,17 +73,48 @@ pub struct BffAsmParser {
     pub intermediates: Vec<ParseIntermediate>,
     pub instructions: Vec<Instruction>,
     pub labels: HashMap<String, usize>,
+    pub data_labels: HashMap<String, Address>,
+    pub globals: Vec<String>,
+    pub externs: Vec<String>,
+    pub object: bool, // keep relocations for bffld instead of requiring every symbol
+    pub relocations: Vec<Relocation>,
     pub string_table: Vec<(Address, String)>,
+    pub version: (u16, u16, u16),
+    pub entry: Option<Address>,
+    pub compact: bool,
+    pub data: Vec<(Address, Vec<Byte>)>,
+    pub bss: Vec<(Address, u32)>,
+    pub file_name: String, // used in the debug info, `.incbin` paths are relative to it
+    pub include_dirs: Vec<PathBuf>, // searched for `.include` and `.incbin` files after the source file's directory
+    pub defines: Vec<(String, String)>, // substituted in every file, see `substitute_defines`
+    pub debug_info: DebugInfo,
+    pub strip_debug: bool, // from `.strip_debug`
+    pub locations: DebugInfo, // from `.loc`, used instead of `debug_info` when there are any
+    pub macros: HashMap<String, Macro>,
+    entry_label: Option<(String, (usize, usize))>,
+    global_spans: Vec<(usize, usize)>, // where each of `globals` was declared
+    label_spans: Vec<(String, Option<(usize, usize)>)>, // where every label was defined, labels from a macro point at its use
+    version_span: Option<(usize, usize)>,
+    data_cursor: Option<Address>,
+    data_started: bool, // false right after `.data`, so the next directive starts a new entry
+    in_data: bool, // between `.data` and `.text`, where labels name data addresses
+    macro_depth: usize,
+    macro_expansions: usize, // numbers the labels of every expansion
+    sources: Vec<Source>,
+    base: usize, // of the source being parsed
+    include_chain: Vec<(String, usize)>, // the file and line of every `.include` being parsed, outermost first
+    include_stack: Vec<(PathBuf, String)>, // canonical paths to find cycles with, and the names to show
+    diagnostics: Vec<Diagnostic>,
 }
 
 macro_rules! get_register_number_from_next_pair {
     ($pairs: ident) => {
-        $pairs.next().unwrap().as_str()[3..].parse::<u8>().unwrap()
+        parse_register(&$pairs.next().unwrap())?
     };
 }
 
 
-pub fn parse_string(string: &str) -> String {
+pub fn parse_string(string: &str) -> Result<String, String> {
     let mut result = String::new();
     let mut chars = string.chars();
     while let Some(c) = chars.next() {
@@ -43,13 +126,67 @@ pub fn parse_string(string: &str) -> String {
                 '\\' => result.push('\\'),
                 '"' => result.push('"'),
                 '\'' => result.push('\''),
-                _ => panic!("Invalid escape sequence"),
+                '0' => result.push('\0'),
+                'x' => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    let byte = u8::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape sequence '\\x{}'", hex))?;
+                    if byte > 0x7f {
+                        return Err("\\x escapes only go up to \\x7f, write the character itself instead".to_string());
+                    }
+                    result.push(byte as char);
+                }
+                c => return Err(format!("invalid escape sequence '\\{}'", c)),
             }
         } else {
             result.push(c);
         }
     }
-    result
+    Ok(result)
+}
+
+fn syntax_error_message(e: pest::error::Error<Rule>) -> String {
+    // between statements pest lists every statement there is
+    match &e.variant {
+        ErrorVariant::ParsingError { positives, .. } if positives.contains(&Rule::EOI) => "expected a statement".to_string(),
+        _ => e.renamed_rules(|rule| format!("{:?}", rule).trim_end_matches('_').replace('_', " ")).variant.message().to_string(),
+    }
+}
+
+pub fn span(pair: &Pair<Rule>) -> (usize, usize) {
+    (pair.as_span().start(), pair.as_span().end())
+}
+
+// the register's number, reg0 is the stack pointer (STACK_POINTER) and reg1 to reg16 are general purpose
+fn parse_register(pair: &Pair<Rule>) -> Result<u8, StatementError> {
+    match pair.as_str()[3..].parse::<u8>() {
+        Ok(register) if (register as usize) < REGISTER_COUNT => Ok(register),
+        _ => Err(StatementError::at(span(pair), format!("'{}' isn't a register, they go from reg0 to reg{}", pair.as_str(), REGISTER_COUNT - 1))),
+    }
+}
+
+fn parse_number<T: FromStr>(pair: &Pair<Rule>) -> Result<T, StatementError> {
+    pair.as_str().parse::<T>().map_err(|_| {
+        let kind = match std::any::type_name::<T>() {
+            "u16" => "16 bits",
+            "i32" => "a signed 32 bit value",
+            _ => "an unsigned 32 bit value",
+        };
+        StatementError::at(span(pair), format!("'{}' doesn't fit in {}", pair.as_str(), kind))
+    })
+}
+
+// a float literal as its bits, either decimal or "0f" followed by the raw bits in hex
+pub fn parse_float(float: &str) -> Bits {
+    match float.strip_prefix("0f") {
+        Some(bits) => u32::from_str_radix(bits, 16).unwrap(),
+        None => float.parse::<f32>().unwrap().to_bits(),
+    }
+}
+
+impl Default for BffAsmParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BffAsmParser {
@@ -58,1128 +195,1641 @@ impl BffAsmParser {
             intermediates: vec![],
             instructions: vec![],
             labels: HashMap::new(),
+            data_labels: HashMap::new(),
+            globals: vec![],
+            externs: vec![],
+            object: false,
+            relocations: vec![],
             string_table: vec![],
+            version: VERSION,
+            entry: None,
+            compact: false,
+            data: vec![],
+            bss: vec![],
+            file_name: "<input>".to_string(),
+            include_dirs: vec![],
+            defines: vec![],
+            debug_info: DebugInfo::new(),
+            strip_debug: false,
+            locations: DebugInfo::new(),
+            entry_label: None,
+            global_spans: vec![],
+            label_spans: vec![],
+            version_span: None,
+            data_cursor: None,
+            data_started: false,
+            in_data: false,
+            macros: HashMap::new(),
+            macro_depth: 0,
+            macro_expansions: 0,
+            sources: vec![],
+            base: 0,
+            include_chain: vec![],
+            include_stack: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    fn located(&self, pair: &Pair<Rule>) -> (usize, usize) {
+        let (start, end) = span(pair);
+        (self.base + start, self.base + end)
+    }
+
+    // a span from `located` in whichever file it's in
+    fn diagnostic(&self, (start, end): (usize, usize), message: &str) -> Diagnostic {
+        let source = self.sources.iter().rev().find(|source| source.base <= start).unwrap();
+        let mut diagnostic = Diagnostic::new(&source.file, &source.text, start - source.base, end - source.base, message);
+        diagnostic.included_from = source.included_from.iter().rev().cloned().collect();
+        diagnostic
+    }
+
+    // next to the file being parsed first, then in the include dirs
+    fn find_file(&self, path: &str) -> PathBuf {
+        let relative = Path::new(&self.file_name).parent().unwrap_or(Path::new(".")).join(path);
+        self.include_dirs.iter()
+            .map(|dir| dir.join(path))
+            .find(|candidate| !relative.exists() && candidate.exists())
+            .unwrap_or(relative)
+    }
+
+    // appends to the data at the cursor, continuing the last entry if nothing came in between
+    fn push_data(&mut self, bytes: &[Byte]) -> Result<(), String> {
+        let address = self.data_cursor.ok_or("data directives need a '.data <address>' before them")?;
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        match self.data.last_mut() {
+            Some((start, data)) if self.data_started && *start + data.len() as Address == address => data.extend_from_slice(bytes),
+            _ => self.data.push((address, bytes.to_vec())),
         }
+        self.data_cursor = Some(address.checked_add(bytes.len() as Address).ok_or("data runs past the end of memory")?);
+        self.data_started = true;
+        Ok(())
     }
 
-    pub fn parse(&mut self, input: &str) -> Result<(), String> {
-        let pairs = BffAsmBareParser::parse(Rule::program, input).unwrap_or_else(|e| panic!("{}", e));
+    pub fn parse(&mut self, input: &str) -> Result<(), AsmError> {
+        if let Ok(path) = Path::new(&self.file_name).canonicalize() {
+            self.include_stack.push((path, self.file_name.clone()));
+        }
+        self.parse_source(input);
+        let mut errors = vec![];
+        let mut error = |span: (usize, usize), message: String| errors.push((span, message));
+
+        if let Some((label, label_span)) = &self.entry_label {
+            match self.labels.get(label) {
+                Some(index) => self.entry = Some(*index as Address),
+                None => error(*label_span, format!("undefined '.entry' label '{}'", label)),
+            }
+        }
+        for (global, global_span) in self.globals.iter().zip(&self.global_spans) {
+            if !self.labels.contains_key(global) && !self.data_labels.contains_key(global) {
+                error(*global_span, format!("'.global' symbol '{}' is never defined", global));
+            }
+        }
+        if let Some(version_span) = self.version_span.filter(|_| self.version < SECTIONED_VERSION) {
+            if self.entry.is_some() || self.entry_label.is_some() || self.compact || !self.data.is_empty() || !self.bss.is_empty() {
+                error(version_span, "'.entry', '.compact' and data directives need version 0.3 or newer".to_string());
+            }
+            if self.object {
+                error(version_span, "objects need version 0.3 or newer".to_string());
+            }
+        }
+
+        for (index, intermediate) in self.intermediates.iter().enumerate() {
+            match intermediate {
+                ParseIntermediate::Instruction(instruction) => {
+                    self.instructions.push(*instruction);
+                }
+                ParseIntermediate::Reference(instruction, symbol, symbol_span) => {
+                    let index = index as Address + 1;
+                    if let Some(value) = self.labels.get(symbol) {
+                        // code moves when objects are linked, data stays where it is
+                        if self.object {
+                            self.relocations.push(Relocation::new(index, symbol));
+                        }
+                        self.instructions.push(instruction.with_address(*value as Address).unwrap());
+                    } else if let Some(value) = self.data_labels.get(symbol) {
+                        self.instructions.push(instruction.with_address(*value).unwrap());
+                    } else if self.externs.contains(symbol) {
+                        if !self.object {
+                            error(*symbol_span, format!("'{}' is '.extern', assemble as an object and link it with bffld", symbol));
+                        }
+                        self.relocations.push(Relocation::new(index, symbol));
+                        self.instructions.push(*instruction);
+                    } else {
+                        error(*symbol_span, format!("undefined symbol '{}'", symbol));
+                    }
+                }
+            }
+        }
+
+        for (span, message) in errors {
+            let diagnostic = self.diagnostic(span, &message);
+            self.diagnostics.push(diagnostic);
+        }
+        if !self.diagnostics.is_empty() {
+            // errors in an included file go where it's included
+            let mut diagnostics = std::mem::take(&mut self.diagnostics);
+            diagnostics.sort_by_key(|diagnostic| {
+                let mut lines: Vec<usize> = diagnostic.included_from.iter().rev().map(|(_, line)| *line).collect();
+                lines.extend([diagnostic.line, diagnostic.column]);
+                lines
+            });
+            return Err(AsmError { diagnostics });
+        }
+        Ok(())
+    }
+
+    // parses one file into the program, an `.include` parses the other file before going on
+    fn parse_source(&mut self, input: &str) {
+        let input = substitute_defines(input, &self.defines);
+        let base = self.sources.last().map_or(0, |source| source.base + source.text.len() + 1);
+        self.sources.push(Source { file: self.file_name.clone(), text: input.clone(), base, included_from: self.include_chain.clone() });
+        let including_base = std::mem::replace(&mut self.base, base);
+        self.parse_statements(&input);
+        self.base = including_base;
+    }
+
+    fn parse_statements(&mut self, input: &str) {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+
+        // a line with a syntax error is blanked out and the file parsed again, so every broken line is
+        // reported. the blanks keep the byte offsets of everything else the same
+        let mut recovered = input.to_string();
+        while let Err(e) = BffAsmBareParser::parse(Rule::program, &recovered) {
+            let (start, end) = match e.location {
+                InputLocation::Pos(position) => (position, position),
+                InputLocation::Span(span) => span,
+            };
+            let diagnostic = self.diagnostic((self.base + start, self.base + end), &syntax_error_message(e));
+            self.diagnostics.push(diagnostic);
+
+            let line = line_starts.partition_point(|line_start| *line_start <= start);
+            let line_start = line_starts[line - 1];
+            let line_end = recovered[line_start..].find('\n').map_or(recovered.len(), |end| line_start + end);
+            if recovered[line_start..line_end].trim().is_empty() {
+                return;
+            }
+            recovered.replace_range(line_start..line_end, &" ".repeat(line_end - line_start));
+        }
+        let pairs = BffAsmBareParser::parse(Rule::program, &recovered).unwrap();
+        let mut current_label: Option<String> = None;
+
         for pair in pairs {
-            match pair.as_rule() {
-                Rule::label => {
-                    let mut inner_rules = pair.into_inner();
-                    let label = inner_rules.next().unwrap().as_str();
+            if pair.as_rule() == Rule::EOI {
+                break;
+            }
+            let statement = span(&pair);
+            if let Err(e) = self.parse_statement(pair, &mut current_label) {
+                let (start, end) = e.span.unwrap_or(statement);
+                let mut diagnostic = self.diagnostic((self.base + start, self.base + end), &e.message);
+                diagnostic.notes.extend(e.note);
+                self.diagnostics.push(diagnostic);
+            }
+
+            // every instruction a statement emitted gets the statement's location
+            let start = statement.0;
+            let line = line_starts.partition_point(|line_start| *line_start <= start);
+            let column = input[line_starts[line - 1]..start].chars().count() + 1;
+            while self.debug_info.locations.len() < self.intermediates.len() {
+                let location = SourceLocation::new(&self.file_name, line as u32, column as u32, current_label.as_deref());
+                self.debug_info.insert(self.debug_info.locations.len() as Address + 1, location);
+            }
+        }
+    }
+
+    // one statement of the program, errors leave the statement out and parsing carries on with the next one
+    fn parse_statement(&mut self, pair: Pair<Rule>, current_label: &mut Option<String>) -> Result<(), StatementError> {
+        match pair.as_rule() {
+            Rule::label => {
+                let label_span = self.located(&pair);
+                let statement = span(&pair);
+                let mut inner_rules = pair.into_inner();
+                let label = inner_rules.next().unwrap().as_str();
+                if self.labels.contains_key(label) || self.data_labels.contains_key(label) {
+                    let mut error = StatementError::at(statement, format!("label '{}' is already defined", label));
+                    error.note = self.label_spans.iter()
+                        .find(|(name, _)| name == label)
+                        .and_then(|(_, first)| *first)
+                        .map(|first| {
+                            let first = self.diagnostic(first, "");
+                            format!("first defined at {}:{}:{}", first.file, first.line, first.column)
+                        });
+                    return Err(error);
+                }
+                self.label_spans.push((label.to_string(), (self.macro_depth == 0).then_some(label_span)));
+                if self.in_data {
+                    self.data_labels.insert(label.to_string(), self.data_cursor.unwrap());
+                } else {
                     self.labels.insert(label.to_string(), self.intermediates.len() + 1);
+                    *current_label = Some(label.to_string());
                 }
-                Rule::move_ => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let value = inner_rules.next().unwrap();
-                    match value.as_rule() {
-                        Rule::operation_type => {
-                            let op_type = value.as_str();
-                            match op_type {
-                                "u" => {
-                                    // unsigned immediate
-                                    let value = inner_rules.next().unwrap().as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::MoveImmediate(dst_reg, value)));
-                                }
-                                "s" => {
-                                    // signed immediate
-                                    let value = inner_rules.next().unwrap().as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::MoveImmediate(dst_reg, value as u32)));
-                                }
-                                "f" => {
-                                    // float immediate
-                                    let value = inner_rules.next().unwrap().as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::MoveImmediate(dst_reg, value.to_bits())));
-                                }
-                                _ => unreachable!()
+            }
+            Rule::move_ => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let value = inner_rules.next().unwrap();
+                match value.as_rule() {
+                    Rule::operation_type => {
+                        let op_type = value.as_str();
+                        match op_type {
+                            "u" => {
+                                // unsigned immediate
+                                let value = parse_number::<u32>(&inner_rules.next().unwrap())?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::MoveImmediate(dst_reg, value)));
                             }
+                            "s" => {
+                                // signed immediate
+                                let value = parse_number::<i32>(&inner_rules.next().unwrap())?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::MoveImmediate(dst_reg, value as u32)));
+                            }
+                            "f" => {
+                                // float immediate
+                                let value = parse_float(inner_rules.next().unwrap().as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::MoveImmediate(dst_reg, value)));
+                            }
+                            _ => unreachable!()
                         }
-                        Rule::register => {
-                            let src_reg = value.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Move(dst_reg, src_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
                     }
-                }
-                Rule::equal => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::float => {
-                            let value = rhs.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::EqualImmediate(dst_reg, lhs_reg, value.to_bits())));
-                        }
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::EqualImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            let value = rhs.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::EqualImmediate(dst_reg, lhs_reg, value as u32)));
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Equal(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+                    Rule::register => {
+                        let src_reg = parse_register(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Move(dst_reg, src_reg)));
                     }
-                }
-                Rule::not_equal => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::float => {
-                            let value = rhs.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqualImmediate(dst_reg, lhs_reg, value.to_bits())));
-                        }
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqualImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            let value = rhs.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqualImmediate(dst_reg, lhs_reg, value as u32)));
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqual(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+                    Rule::symbol_address => {
+                        let symbol = value.into_inner().next().unwrap();
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::MoveImmediate(dst_reg, 0), symbol.as_str().to_string(), self.located(&symbol)));
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::not => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let src = inner_rules.next().unwrap();
-                    match src.as_rule() {
-                        Rule::register => {
-                            let src_reg = src.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Not(dst_reg, src_reg)));
-                        }
-                        Rule::float => {
-                            let value = src.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotImmediate(dst_reg, value.to_bits())));
-                        }
-                        Rule::unsigned => {
-                            let value = src.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotImmediate(dst_reg, value)));
-                        }
-                        Rule::signed => {
-                            let value = src.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotImmediate(dst_reg, value as u32)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::equal => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::float => {
+                        let value = parse_float(rhs.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::EqualImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::EqualImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::EqualImmediate(dst_reg, lhs_reg, value as u32)));
                     }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Equal(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::and => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::float => {
-                            let value = rhs.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::AndImmediate(dst_reg, lhs_reg, value.to_bits())));
-                        }
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::AndImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            let value = rhs.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::AndImmediate(dst_reg, lhs_reg, value as u32)));
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::And(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::not_equal => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::float => {
+                        let value = parse_float(rhs.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqualImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqualImmediate(dst_reg, lhs_reg, value)));
                     }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqualImmediate(dst_reg, lhs_reg, value as u32)));
+                    }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotEqual(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::or => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::float => {
-                            let value = rhs.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::OrImmediate(dst_reg, lhs_reg, value.to_bits())));
-                        }
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::OrImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            let value = rhs.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::OrImmediate(dst_reg, lhs_reg, value as u32)));
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Or(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::not => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let src = inner_rules.next().unwrap();
+                match src.as_rule() {
+                    Rule::register => {
+                        let src_reg = parse_register(&src)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Not(dst_reg, src_reg)));
+                    }
+                    Rule::float => {
+                        let value = parse_float(src.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotImmediate(dst_reg, value)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&src)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotImmediate(dst_reg, value)));
                     }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&src)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::NotImmediate(dst_reg, value as u32)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::xor => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::float => {
-                            let value = rhs.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::XorImmediate(dst_reg, lhs_reg, value.to_bits())));
-                        }
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::XorImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            let value = rhs.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::XorImmediate(dst_reg, lhs_reg, value as u32)));
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Xor(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::and => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::float => {
+                        let value = parse_float(rhs.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::AndImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::AndImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::AndImmediate(dst_reg, lhs_reg, value as u32)));
                     }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::And(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::shift_left => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftLeftImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            panic!("Signed shift left is not supported")
-                        }
-                        Rule::float => {
-                            panic!("Float shift left is not supported")
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftLeft(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::or => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::float => {
+                        let value = parse_float(rhs.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::OrImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::OrImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::OrImmediate(dst_reg, lhs_reg, value as u32)));
                     }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Or(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::shift_right => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match rhs.as_rule() {
-                        Rule::unsigned => {
-                            let value = rhs.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftRightImmediate(dst_reg, lhs_reg, value)));
-                        }
-                        Rule::signed => {
-                            panic!("Signed shift right is not supported")
-                        }
-                        Rule::float => {
-                            panic!("Float shift right is not supported")
-                        }
-                        Rule::register => {
-                            let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftRight(dst_reg, lhs_reg, rhs_reg)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::xor => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::float => {
+                        let value = parse_float(rhs.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::XorImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::XorImmediate(dst_reg, lhs_reg, value)));
                     }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::XorImmediate(dst_reg, lhs_reg, value as u32)));
+                    }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Xor(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::jump => {
-                    let mut inner_rules = pair.into_inner();
-                    let label = inner_rules.next().unwrap().as_str();
-                    self.intermediates.push(ParseIntermediate::Jump(label.to_string()));
+            }
+            Rule::shift_left => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftLeftImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::signed => {
+                        return Err("Signed shift left is not supported".into())
+                    }
+                    Rule::float => {
+                        return Err("Float shift left is not supported".into())
+                    }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftLeft(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::jnz => {
-                    let mut inner_rules = pair.into_inner();
-                    let register = get_register_number_from_next_pair!(inner_rules);
-                    let label = inner_rules.next().unwrap().as_str();
-                    self.intermediates.push(ParseIntermediate::Jnz(register, label.to_string()));
+            }
+            Rule::shift_right => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match rhs.as_rule() {
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftRightImmediate(dst_reg, lhs_reg, value)));
+                    }
+                    Rule::signed => {
+                        return Err("Signed shift right is not supported".into())
+                    }
+                    Rule::float => {
+                        return Err("Float shift right is not supported".into())
+                    }
+                    Rule::register => {
+                        let rhs_reg = parse_register(&rhs)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::ShiftRight(dst_reg, lhs_reg, rhs_reg)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::push => {
-                    let mut inner_rules = pair.into_inner();
-                    let value = inner_rules.next().unwrap();
-                    match value.as_rule() {
-                        Rule::register => {
-                            let reg = value.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Push(reg)));
-                        }
-                        Rule::unsigned => {
-                            let value = value.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::PushImmediate(value)));
-                        }
-                        Rule::signed => {
-                            let value = value.as_str().parse::<i32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::PushImmediate(value as u32)));
-                        }
-                        Rule::float => {
-                            let value = value.as_str().parse::<f32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::PushImmediate(value.to_bits())));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::jump => {
+                let mut inner_rules = pair.into_inner();
+                let target = inner_rules.next().unwrap();
+                match target.as_rule() {
+                    Rule::register => {
+                        let reg = parse_register(&target)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Jump(reg)));
                     }
+                    Rule::address => {
+                        let address = parse_number::<u32>(&target)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::JumpImmediate(address)));
+                    }
+                    Rule::identifier => {
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::JumpImmediate(0), target.as_str().to_string(), self.located(&target)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::pop => {
-                    let mut inner_rules = pair.into_inner();
-                    let reg = get_register_number_from_next_pair!(inner_rules);
-                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Pop(reg)));
+            }
+            Rule::jnz => {
+                let mut inner_rules = pair.into_inner();
+                let register = get_register_number_from_next_pair!(inner_rules);
+                let target = inner_rules.next().unwrap();
+                match target.as_rule() {
+                    Rule::register => {
+                        let reg = parse_register(&target)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::JumpNotZero(register, reg)));
+                    }
+                    Rule::address => {
+                        let address = parse_number::<u32>(&target)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::JumpNotZeroImmediate(register, address)));
+                    }
+                    Rule::identifier => {
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::JumpNotZeroImmediate(register, 0), target.as_str().to_string(), self.located(&target)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::call => {
-                    let mut inner_rules = pair.into_inner();
-                    let label = inner_rules.next().unwrap().as_str();
-                    self.intermediates.push(ParseIntermediate::Call(label.to_string()));
+            }
+            Rule::push => {
+                let mut inner_rules = pair.into_inner();
+                let value = inner_rules.next().unwrap();
+                match value.as_rule() {
+                    Rule::register => {
+                        let reg = parse_register(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Push(reg)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::PushImmediate(value)));
+                    }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::PushImmediate(value as u32)));
+                    }
+                    Rule::float => {
+                        let value = parse_float(value.as_str());
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::PushImmediate(value)));
+                    }
+                    Rule::symbol_address => {
+                        let symbol = value.into_inner().next().unwrap();
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::PushImmediate(0), symbol.as_str().to_string(), self.located(&symbol)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::ret => {
-                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Return));
+            }
+            Rule::pop => {
+                let mut inner_rules = pair.into_inner();
+                let reg = get_register_number_from_next_pair!(inner_rules);
+                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Pop(reg)));
+            }
+            Rule::call => {
+                let mut inner_rules = pair.into_inner();
+                let target = inner_rules.next().unwrap();
+                match target.as_rule() {
+                    Rule::address => {
+                        let address = parse_number::<u32>(&target)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Call(address)));
+                    }
+                    Rule::identifier => {
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::Call(0), target.as_str().to_string(), self.located(&target)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::syscall => {
-                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SystemCall));
+            }
+            Rule::ret => {
+                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Return));
+            }
+            Rule::syscall => {
+                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SystemCall));
+            }
+            Rule::nop => {
+                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Nop));
+            }
+            Rule::halt => {
+                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Halt));
+            }
+            Rule::exit => {
+                let mut inner_rules = pair.into_inner();
+                let value = inner_rules.next().unwrap();
+                match value.as_rule() {
+                    Rule::register => {
+                        let reg = parse_register(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Exit(reg)));
+                    }
+                    Rule::unsigned => {
+                        let value = parse_number::<u32>(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::ExitImmediate(value)));
+                    }
+                    Rule::signed => {
+                        let value = parse_number::<i32>(&value)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::ExitImmediate(value as u32)));
+                    }
+                    Rule::float => {
+                        return Err("Float exit code is not supported".into())
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::store => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst = inner_rules.next().unwrap();
-                    let src_reg = get_register_number_from_next_pair!(inner_rules);
-                    let size = inner_rules.next().unwrap().as_str().parse::<u8>().unwrap();
+            }
+            Rule::store => {
+                let mut inner_rules = pair.into_inner();
+                let dst = inner_rules.next().unwrap();
+                let src_reg = get_register_number_from_next_pair!(inner_rules);
+                let size = inner_rules.next().unwrap().as_str().parse::<u8>().unwrap();
 
 
-                    match dst.as_rule(){
-                        Rule::register => {
-                            let dst_reg = dst.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Store(dst_reg, src_reg, size)));
-                        }
-                        Rule::unsigned => {
-                            let dst = dst.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::DirectStore(dst, src_reg, size)));
-                        }
-                        path => unreachable!("{:?}", path)
+                match dst.as_rule(){
+                    Rule::register => {
+                        let dst_reg = parse_register(&dst)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Store(dst_reg, src_reg, size)));
                     }
+                    Rule::unsigned => {
+                        let dst = parse_number::<u32>(&dst)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::DirectStore(dst, src_reg, size)));
+                    }
+                    Rule::identifier => {
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::DirectStore(0, src_reg, size), dst.as_str().to_string(), self.located(&dst)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::load => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let src = inner_rules.next().unwrap();
-                    let size = inner_rules.next().unwrap().as_str().parse::<u8>().unwrap();
-
-                    match src.as_rule(){
-                        Rule::register => {
-                            let src_reg = src.as_str()[3..].parse::<u8>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::Load(dst_reg, src_reg, size)));
-                        }
-                        Rule::unsigned => {
-                            let src = src.as_str().parse::<u32>().unwrap();
-                            self.intermediates.push(ParseIntermediate::Instruction(Instruction::DirectLoad(dst_reg, src, size)));
-                        }
-                        path => unreachable!("{:?}", path)
+            }
+            Rule::load => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let src = inner_rules.next().unwrap();
+                let size = inner_rules.next().unwrap().as_str().parse::<u8>().unwrap();
+
+                match src.as_rule(){
+                    Rule::register => {
+                        let src_reg = parse_register(&src)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::Load(dst_reg, src_reg, size)));
                     }
+                    Rule::unsigned => {
+                        let src = parse_number::<u32>(&src)?;
+                        self.intermediates.push(ParseIntermediate::Instruction(Instruction::DirectLoad(dst_reg, src, size)));
+                    }
+                    Rule::identifier => {
+                        self.intermediates.push(ParseIntermediate::Reference(Instruction::DirectLoad(dst_reg, 0, size), src.as_str().to_string(), self.located(&src)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::add => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::AddImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed add is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float add is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Add(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::add => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::AddImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed add is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float add is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Add(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedAddImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedAddImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float add is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedAdd(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedAddImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedAddImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float add is not supported".into())
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedAdd(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned add is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed add is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatAddImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatAdd(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned add is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed add is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatAddImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatAdd(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::sub => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SubImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed sub is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float sub is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Sub(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::sub => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SubImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed sub is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float sub is not supported".into())
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Sub(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedSubImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedSubImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float sub is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedSub(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedSubImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedSubImmediate(dst_reg, lhs_reg, value as u32)));
                             }
+                            Rule::float => {
+                                return Err("Float sub is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedSub(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned sub is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed sub is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatSubImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatSub(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned sub is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed sub is not supported".into())
                             }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatSubImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatSub(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::mul => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::MulImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed mul is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float mul is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Mul(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::mul => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::MulImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed mul is not supported".into())
                             }
+                            Rule::float => {
+                                return Err("Float mul is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Mul(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMulImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMulImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float mul is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMul(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMulImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMulImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float mul is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMul(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned mul is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed mul is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatMulImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatMul(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned mul is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed mul is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatMulImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatMul(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::div => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::DivImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed div is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float div is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Div(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::div => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::DivImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed div is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float div is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Div(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float div is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDiv(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float div is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDiv(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned div is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed div is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDivImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDiv(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned div is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed div is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDivImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDiv(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::mod_ => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::ModImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed mod is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float mod is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::Mod(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::mod_ => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::ModImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed mod is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float mod is not supported".into())
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::Mod(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedModImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedModImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float mod is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMod(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedModImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedModImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float mod is not supported".into())
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedMod(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => {
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned mod is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed mod is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatModImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatMod(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => {
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned mod is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed mod is not supported".into())
                             }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatModImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatMod(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::divmod => {
-                    let mut inner_rules = pair.into_inner();
-                    let div_dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let mod_dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => { // Unsigned
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::DivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed divmod is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float divmod is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::DivMod(div_dst_reg, mod_dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::divmod => {
+                let mut inner_rules = pair.into_inner();
+                let div_dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let mod_dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => { // Unsigned
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::DivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed divmod is not supported".into())
                             }
+                            Rule::float => {
+                                return Err("Float divmod is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::DivMod(div_dst_reg, mod_dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => { // Signed
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float divmod is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivMod(div_dst_reg, mod_dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => { // Signed
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value as u32)));
                             }
+                            Rule::float => {
+                                return Err("Float divmod is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedDivMod(div_dst_reg, mod_dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => { // Float
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned divmod is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed divmod is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDivMod(div_dst_reg, mod_dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => { // Float
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned divmod is not supported".into())
                             }
+                            Rule::signed => {
+                                return Err("Signed divmod is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDivModImmediate(div_dst_reg, mod_dst_reg, lhs_reg, value)));
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatDivMod(div_dst_reg, mod_dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::greater_than => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => { // Unsigned
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThanImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed greater than is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float greater than is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThan(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::greater_than => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => { // Unsigned
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThanImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::signed => {
+                                return Err("Signed greater than is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float greater than is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThan(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => { // Signed
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float greater than is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThan(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => { // Signed
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float greater than is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThan(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => { // Float
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned greater than is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed greater than is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThanImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThan(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => { // Float
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned greater than is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed greater than is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThanImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThan(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::less_than => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => { // Unsigned
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThanImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed less than is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float less than is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThan(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::less_than => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => { // Unsigned
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThanImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed less than is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float less than is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThan(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => { // Signed
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float less than is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThan(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => { // Signed
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float less than is not supported".into())
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThan(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => { // Float
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned less than is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed less than is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThanImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThan(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => { // Float
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned less than is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed less than is not supported".into())
                             }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThanImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThan(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::greater_than_or_equal => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => { // Unsigned
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThanOrEqualImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed greater than or equal is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float greater than or equal is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::greater_than_or_equal => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => { // Unsigned
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThanOrEqualImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed greater than or equal is not supported".into())
+                            }
+                            Rule::float => {
+                                return Err("Float greater than or equal is not supported".into())
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::GreaterThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => { // Signed
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanOrEqualImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanOrEqualImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float greater than or equal is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => { // Signed
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanOrEqualImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanOrEqualImmediate(dst_reg, lhs_reg, value as u32)));
                             }
+                            Rule::float => {
+                                return Err("Float greater than or equal is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedGreaterThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => { // Float
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned greater than or equal is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed greater than or equal is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThanOrEqualImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => { // Float
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned greater than or equal is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed greater than or equal is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThanOrEqualImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatGreaterThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::less_than_or_equal => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let lhs_reg = get_register_number_from_next_pair!(inner_rules);
-                    let rhs = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => { // Unsigned
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThanOrEqualImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    panic!("Signed less than or equal is not supported")
-                                }
-                                Rule::float => {
-                                    panic!("Float less than or equal is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::less_than_or_equal => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let lhs_reg = get_register_number_from_next_pair!(inner_rules);
+                let rhs = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => { // Unsigned
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThanOrEqualImmediate(dst_reg, lhs_reg, value)));
+                            }
+                            Rule::signed => {
+                                return Err("Signed less than or equal is not supported".into())
                             }
+                            Rule::float => {
+                                return Err("Float less than or equal is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::LessThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "s" => { // Signed
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    let value = rhs.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanOrEqualImmediate(dst_reg, lhs_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = rhs.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanOrEqualImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float less than or equal is not supported")
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "s" => { // Signed
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanOrEqualImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanOrEqualImmediate(dst_reg, lhs_reg, value as u32)));
+                            }
+                            Rule::float => {
+                                return Err("Float less than or equal is not supported".into())
+                            }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedLessThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => { // Float
-                            match rhs.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned less than or equal is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed less than or equal is not supported")
-                                }
-                                Rule::float => {
-                                    let value = rhs.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThanOrEqualImmediate(dst_reg, lhs_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let rhs_reg = rhs.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => { // Float
+                        match rhs.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned less than or equal is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed less than or equal is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(rhs.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThanOrEqualImmediate(dst_reg, lhs_reg, value)));
                             }
+                            Rule::register => {
+                                let rhs_reg = parse_register(&rhs)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatLessThanOrEqual(dst_reg, lhs_reg, rhs_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
                 }
-                Rule::negate => {
-                    let mut inner_rules = pair.into_inner();
-                    let dst_reg = get_register_number_from_next_pair!(inner_rules);
-                    let op_type = inner_rules.next().unwrap().as_str();
-                    let val = inner_rules.next().unwrap();
-
-                    match op_type {
-                        "u" => { // Unsigned
-                            panic!("Unsigned negate is not supported")
-                        }
-                        "s" => { // Signed
-                            match val.as_rule() {
-                                Rule::unsigned => {
-                                    let value = val.as_str().parse::<u32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedNegateImmediate(dst_reg, value)));
-                                }
-                                Rule::signed => {
-                                    let value = val.as_str().parse::<i32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedNegateImmediate(dst_reg, value as u32)));
-                                }
-                                Rule::float => {
-                                    panic!("Float negate is not supported")
-                                }
-                                Rule::register => {
-                                    let val_reg = val.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedNegate(dst_reg, val_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+            }
+            Rule::negate => {
+                let mut inner_rules = pair.into_inner();
+                let dst_reg = get_register_number_from_next_pair!(inner_rules);
+                let op_type = inner_rules.next().unwrap().as_str();
+                let val = inner_rules.next().unwrap();
+
+                match op_type {
+                    "u" => { // Unsigned
+                        return Err("Unsigned negate is not supported".into())
+                    }
+                    "s" => { // Signed
+                        match val.as_rule() {
+                            Rule::unsigned => {
+                                let value = parse_number::<u32>(&val)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedNegateImmediate(dst_reg, value)));
+                            }
+                            Rule::signed => {
+                                let value = parse_number::<i32>(&val)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedNegateImmediate(dst_reg, value as u32)));
                             }
+                            Rule::float => {
+                                return Err("Float negate is not supported".into())
+                            }
+                            Rule::register => {
+                                let val_reg = parse_register(&val)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::SignedNegate(dst_reg, val_reg)));
+                            }
+                            path => unreachable!("{:?}", path)
                         }
-                        "f" => { // Float
-                            match val.as_rule() {
-                                Rule::unsigned => {
-                                    panic!("Unsigned negate is not supported")
-                                }
-                                Rule::signed => {
-                                    panic!("Signed negate is not supported")
-                                }
-                                Rule::float => {
-                                    let value = val.as_str().parse::<f32>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatNegateImmediate(dst_reg, value as u32)));
-                                }
-                                Rule::register => {
-                                    let val_reg = val.as_str()[3..].parse::<u8>().unwrap();
-                                    self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatNegate(dst_reg, val_reg)));
-                                }
-                                path => unreachable!("{:?}", path)
+                    }
+                    "f" => { // Float
+                        match val.as_rule() {
+                            Rule::unsigned => {
+                                return Err("Unsigned negate is not supported".into())
+                            }
+                            Rule::signed => {
+                                return Err("Signed negate is not supported".into())
+                            }
+                            Rule::float => {
+                                let value = parse_float(val.as_str());
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatNegateImmediate(dst_reg, value)));
+                            }
+                            Rule::register => {
+                                let val_reg = parse_register(&val)?;
+                                self.intermediates.push(ParseIntermediate::Instruction(Instruction::FloatNegate(dst_reg, val_reg)));
                             }
+                            path => unreachable!("{:?}", path)
                         }
-                        path => unreachable!("{:?}", path)
                     }
+                    path => unreachable!("{:?}", path)
+                }
+            }
+            Rule::string_store => {
+                if !self.intermediates.is_empty() {
+                    return Err("string stores must be at the top of the file".into());
                 }
-                Rule::string_store => {
-                    if self.intermediates.len() > 0 {
-                        panic!("String store must be at the top of the file");
-                    }
 
-                    let mut inner_rules = pair.into_inner();
-                    let address = inner_rules.next().unwrap().as_str().parse::<u32>().unwrap();
-                    let string = inner_rules.next().unwrap().as_str();
+                let mut inner_rules = pair.into_inner();
+                let address = parse_number::<u32>(&inner_rules.next().unwrap())?;
+                let string = inner_rules.next().unwrap();
+                let text = string.as_str();
+                let text = parse_string(&text[1..text.len()-1])
+                    .map_err(|message| StatementError::at(span(&string), message))?;
 
-                    self.string_table.push(
-                        (
-                            address,
-                            parse_string(&string[1..string.len()-1])
-                        )
-                    );
+                self.string_table.push((address, text));
+            }
+            Rule::version => {
+                self.version_span = Some(self.located(&pair));
+                let mut inner_rules = pair.into_inner();
+                let major = parse_number::<u16>(&inner_rules.next().unwrap())?;
+                let minor = parse_number::<u16>(&inner_rules.next().unwrap())?;
+                let patch = parse_number::<u16>(&inner_rules.next().unwrap())?;
+                self.version = (major, minor, patch);
+            }
+            Rule::entry => {
+                let mut inner_rules = pair.into_inner();
+                let target = inner_rules.next().unwrap();
+                match target.as_rule() {
+                    Rule::address => {
+                        self.entry = Some(parse_number::<u32>(&target)?);
+                    }
+                    Rule::identifier => {
+                        self.entry_label = Some((target.as_str().to_string(), self.located(&target)));
+                    }
+                    path => unreachable!("{:?}", path)
                 }
+            }
 
-                Rule::EOI => {
-                    break
+            Rule::compact => {
+                self.compact = true;
+            }
+            Rule::data => {
+                let address = parse_number::<u32>(&pair.into_inner().next().unwrap())?;
+                self.data_cursor = Some(address);
+                self.data_started = false;
+                self.in_data = true;
+            }
+            Rule::text => {
+                self.in_data = false;
+            }
+            Rule::global => {
+                let global = pair.into_inner().next().unwrap();
+                self.globals.push(global.as_str().to_string());
+                self.global_spans.push(self.located(&global));
+            }
+            Rule::extern_ => {
+                self.externs.push(pair.into_inner().next().unwrap().as_str().to_string());
+            }
+            Rule::bytes => {
+                let mut bytes = vec![];
+                for value in pair.into_inner() {
+                    let byte = match value.as_str().parse::<i64>() {
+                        Ok(byte @ -128..=255) => byte as u8,
+                        _ => return Err(StatementError::at(span(&value), format!("'.bytes' value {} doesn't fit in a byte", value.as_str()))),
+                    };
+                    bytes.push(byte);
                 }
-                path => unreachable!("{:?}", path)
+                self.push_data(&bytes)?;
             }
-        }
-
-        for intermediate in self.intermediates.iter() {
-            match intermediate {
-                ParseIntermediate::Instruction(instruction) => {
-                    self.instructions.push(instruction.clone());
+            Rule::word => {
+                let mut bytes = vec![];
+                for value in pair.into_inner() {
+                    let word = match value.as_str().parse::<i64>() {
+                        Ok(word @ -0x8000_0000..=0xffff_ffff) => word as u32,
+                        _ => return Err(StatementError::at(span(&value), format!("'.word' value {} doesn't fit in 32 bits", value.as_str()))),
+                    };
+                    bytes.extend_from_slice(&word.to_le_bytes());
+                }
+                self.push_data(&bytes)?;
+            }
+            Rule::float_data => {
+                let mut bytes = vec![];
+                for value in pair.into_inner() {
+                    bytes.extend_from_slice(&parse_float(value.as_str()).to_le_bytes());
+                }
+                self.push_data(&bytes)?;
+            }
+            Rule::zero => {
+                let size = parse_number::<u32>(&pair.into_inner().next().unwrap())?;
+                let address = self.data_cursor.ok_or("'.zero' needs a '.data <address>' before it")?;
+                match self.bss.last_mut() {
+                    Some((start, length)) if self.data_started && *start + *length == address => *length += size,
+                    _ => self.bss.push((address, size)),
+                }
+                self.data_cursor = Some(address.checked_add(size).ok_or("'.zero' runs past the end of memory")?);
+                self.data_started = true;
+            }
+            Rule::incbin => {
+                let string = pair.into_inner().next().unwrap();
+                let path_error = |message| StatementError::at(span(&string), message);
+                let path = string.as_str();
+                let path = parse_string(&path[1..path.len() - 1]).map_err(path_error)?;
+                let path = self.find_file(&path);
+                let bytes = std::fs::read(&path)
+                    .map_err(|e| path_error(format!("couldn't read '.incbin' file '{}': {}", path.display(), e)))?;
+                self.push_data(&bytes)?;
+            }
+            Rule::strip_debug => {
+                self.strip_debug = true;
+            }
+            Rule::loc => {
+                let mut inner_rules = pair.into_inner();
+                let index = parse_number::<u32>(&inner_rules.next().unwrap())?;
+                let file = inner_rules.next().unwrap();
+                let file = parse_string(&file.as_str()[1..file.as_str().len() - 1]).map_err(|message| StatementError::at(span(&file), message))?;
+                let line = parse_number::<u32>(&inner_rules.next().unwrap())?;
+                let column = parse_number::<u32>(&inner_rules.next().unwrap())?;
+                let label = inner_rules.next().map(|label| label.as_str());
+                self.locations.insert(index, SourceLocation::new(&file, line, column, label));
+            }
+            Rule::include => {
+                if self.macro_depth > 0 {
+                    return Err("'.include' can't be used inside a macro".into());
                 }
-                ParseIntermediate::Jump(jmp_label) => {
-                    let jmp_instruction = Instruction::JumpImmediate(self.labels.get(&*jmp_label).unwrap().clone() as Address);
-                    self.instructions.push(jmp_instruction);
+                let line = pair.as_span().start_pos().line_col().0;
+                let string = pair.into_inner().next().unwrap();
+                let path_error = |message| StatementError::at(span(&string), message);
+                let path = string.as_str();
+                let path = self.find_file(&parse_string(&path[1..path.len() - 1]).map_err(path_error)?);
+                let file = path.to_string_lossy().to_string();
+                let (canonical, source) = path.canonicalize()
+                    .and_then(|canonical| Ok((canonical, std::fs::read_to_string(&path)?)))
+                    .map_err(|e| path_error(format!("couldn't read '.include' file '{}': {}", file, e)))?;
+                if let Some(first) = self.include_stack.iter().position(|(included, _)| *included == canonical) {
+                    let cycle: Vec<&str> = self.include_stack[first..].iter().map(|(_, name)| name.as_str()).chain([file.as_str()]).collect();
+                    return Err(path_error(format!("include cycle: {}", cycle.join(" -> "))));
                 }
-                ParseIntermediate::Jnz(reg, jmp_label) => {
-                    let jmp_instruction = Instruction::JumpNotZeroImmediate(*reg, self.labels.get(&*jmp_label).unwrap().clone() as Address);
-                    self.instructions.push(jmp_instruction);
+
+                self.include_chain.push((self.file_name.clone(), line));
+                self.include_stack.push((canonical, file.clone()));
+                let including = std::mem::replace(&mut self.file_name, file);
+                self.parse_source(&source);
+                self.file_name = including;
+                self.include_stack.pop();
+                self.include_chain.pop();
+            }
+            Rule::macro_ => {
+                let mut inner_rules = pair.into_inner();
+                let name = inner_rules.next().unwrap();
+                let mut definition = Macro { parameters: vec![], body: String::new(), file: self.file_name.clone(), line: 0 };
+                for inner in inner_rules {
+                    match inner.as_rule() {
+                        Rule::identifier => definition.parameters.push(inner.as_str().to_string()),
+                        Rule::macro_body => {
+                            definition.body = inner.as_str().to_string();
+                            definition.line = inner.as_span().start_pos().line_col().0;
+                        }
+                        path => unreachable!("{:?}", path)
+                    }
                 }
-                ParseIntermediate::Call(jmp_label) => {
-                    let jmp_instruction = Instruction::Call(self.labels.get(&*jmp_label).unwrap().clone() as Address);
-                    self.instructions.push(jmp_instruction);
+                if self.macros.contains_key(name.as_str()) {
+                    return Err(StatementError::at(span(&name), format!("macro '{}' is already defined", name.as_str())));
                 }
+                self.macros.insert(name.as_str().to_string(), definition);
+            }
+            Rule::macro_call => {
+                let statement = self.located(&pair);
+                let mut inner_rules = pair.into_inner();
+                let name = inner_rules.next().unwrap();
+                let arguments: Vec<String> = inner_rules.map(|argument| argument.as_str().to_string()).collect();
+                self.expand_macro(&name, &arguments, statement, current_label)?;
             }
-        }
 
+            path => unreachable!("{:?}", path)
+        }
         Ok(())
     }
+
+    // parses the body of a macro with the arguments in place of its parameters. labels in the body are
+    // renamed for every expansion, so a macro with a loop can be used more than once
+    fn expand_macro(&mut self, name: &Pair<Rule>, arguments: &[String], statement: (usize, usize), current_label: &mut Option<String>) -> Result<(), StatementError> {
+        let Some(definition) = self.macros.get(name.as_str()).cloned() else {
+            return Err(StatementError::at(span(name), format!("unknown instruction or macro '{}'", name.as_str())));
+        };
+        let name = name.as_str();
+        if definition.parameters.len() != arguments.len() {
+            return Err(format!("macro '{}' takes {} arguments, not {}", name, definition.parameters.len(), arguments.len()).into());
+        }
+        if self.macro_depth == MAX_MACRO_DEPTH {
+            return Err(format!("macro '{}' expands more than {} levels deep", name, MAX_MACRO_DEPTH).into());
+        }
+
+        // lines stay where they are, so errors can name the line of the definition they come from
+        let file_name = self.file_name.clone();
+        let in_macro = |body: &str, offset: usize, message: String| {
+            let line = definition.line + body[..offset].matches('\n').count();
+            let place = match definition.file == file_name {
+                true => format!("line {}", line),
+                false => format!("{}:{}", definition.file, line),
+            };
+            StatementError { message: format!("{} (in macro '{}' at {})", message, name, place), span: None, expanded: true, note: None }
+        };
+        let defines: Vec<(String, String)> = definition.parameters.iter().cloned().zip(arguments.iter().cloned()).collect();
+        let body = substitute_defines(&definition.body, &defines);
+        let syntax_error = |e: pest::error::Error<Rule>| {
+            let offset = match e.location {
+                InputLocation::Pos(position) => position,
+                InputLocation::Span((start, _)) => start,
+            };
+            in_macro(&body, offset, syntax_error_message(e))
+        };
+        self.macro_expansions += 1;
+        let labels: Vec<(String, String)> = BffAsmBareParser::parse(Rule::program, &body).map_err(syntax_error)?
+            .filter(|pair| pair.as_rule() == Rule::label)
+            .map(|pair| pair.into_inner().next().unwrap().as_str().to_string())
+            .map(|label| (label.clone(), format!("{}_{}_{}", name, self.macro_expansions, label)))
+            .collect();
+        let body = substitute_defines(&body, &labels);
+
+        let first_intermediate = self.intermediates.len();
+        let first_global = self.global_spans.len();
+        let first_label = self.label_spans.len();
+        let had_entry_label = self.entry_label.is_some();
+        let version_span = self.version_span;
+        // the expansion's local labels don't name what comes after the macro
+        let label_before = current_label.clone();
+        self.macro_depth += 1;
+        let mut result = Ok(());
+        for pair in BffAsmBareParser::parse(Rule::program, &body).unwrap() {
+            if pair.as_rule() == Rule::EOI {
+                break;
+            }
+            let start = pair.as_span().start();
+            match self.parse_statement(pair, current_label) {
+                Err(e) if e.expanded => result = Err(e),
+                Err(e) => result = Err(StatementError { note: e.note, ..in_macro(&body, e.span.map_or(start, |span| span.0), e.message) }),
+                Ok(()) => continue,
+            }
+            break;
+        }
+        self.macro_depth -= 1;
+        *current_label = label_before;
+
+        // the spans point into the expansion, errors about them are shown at the use of the macro instead
+        for intermediate in &mut self.intermediates[first_intermediate..] {
+            if let ParseIntermediate::Reference(_, _, symbol_span) = intermediate {
+                *symbol_span = statement;
+            }
+        }
+        for global_span in &mut self.global_spans[first_global..] {
+            *global_span = statement;
+        }
+        if self.macro_depth == 0 {
+            for (_, label_span) in &mut self.label_spans[first_label..] {
+                *label_span = Some(statement);
+            }
+        }
+        if let (false, Some((_, label_span))) = (had_entry_label, &mut self.entry_label) {
+            *label_span = statement;
+        }
+        if self.version_span != version_span {
+            self.version_span = Some(statement);
+        }
+        result
+    }
 }
\ No newline at end of file
@@ -0,0 +1,60 @@
+// replaces every identifier that has a define (`bffasm -D NAME=VALUE`) with its value, string literals,
+// comments and numbers (`0f7fc00000`) are left alone. lines stay where they are as long as values don't have newlines
+pub fn substitute_defines(source: &str, defines: &[(String, String)]) -> String {
+    if defines.is_empty() {
+        return source.to_string();
+    }
+    let mut output = String::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '"' => {
+                output.push(c);
+                while let Some((_, c)) = chars.next() {
+                    output.push(c);
+                    match c {
+                        '\\' => output.extend(chars.next().map(|(_, c)| c)),
+                        '"' | '\n' => break,
+                        _ => {}
+                    }
+                }
+            }
+            // a quote in a comment doesn't start a string
+            ';' => {
+                output.push(c);
+                while let Some((_, c)) = chars.next_if(|(_, c)| *c != '\n') {
+                    output.push(c);
+                }
+            }
+            '/' if source[start..].starts_with("//") => {
+                output.push(c);
+                while let Some((_, c)) = chars.next_if(|(_, c)| *c != '\n') {
+                    output.push(c);
+                }
+            }
+            '/' if source[start..].starts_with("/*") => {
+                let end = source[start + 2..].find("*/").map_or(source.len(), |end| start + 2 + end + 2);
+                output.push_str(&source[start..end]);
+                while chars.next_if(|(index, _)| *index < end).is_some() {}
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let number = c.is_ascii_digit();
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || *c == '_' || (number && *c == '.')) {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                let word = &source[start..end];
+                match defines.iter().rev().find(|(name, _)| name == word) {
+                    Some((_, value)) if !number => output.push_str(value),
+                    _ => output.push_str(word),
+                }
+            }
+            c => output.push(c),
+        }
+    }
+    output
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use bffasm::AssemblerConfig;
+use bffasm::preprocessor::substitute_defines;
+use bffcore::constants::instructions::Instruction;
+
+#[test]
+fn defines_replace_whole_identifiers() {
+    let defines = vec![("SIZE".to_string(), "16".to_string()), ("name".to_string(), "other".to_string())];
+    assert_eq!(
+        substitute_defines("reg1 = u SIZE\n0 <- \"SIZE \\\"name\"\njmp name\nreg2 = f 1.5\nSIZES:", &defines),
+        "reg1 = u 16\n0 <- \"SIZE \\\"name\"\njmp other\nreg2 = f 1.5\nSIZES:",
+    );
+
+    let config = AssemblerConfig::new().define("LIMIT", "3").define("LIMIT", "4");
+    let program = bffasm::assemble_with_config("<input>", "reg1 = u LIMIT\nexit reg1", &config).unwrap();
+    assert_eq!(program.instructions[0], Instruction::MoveImmediate(1, 4));
+}
+
+#[test]
+fn incbin_searches_include_dirs() {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("include_dir");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("table.bin"), [1, 2, 3]).unwrap();
+
+    let source = ".data 16\n.incbin \"table.bin\"\nhalt\n";
+    assert!(bffasm::assemble(source).is_err());
+    let program = bffasm::assemble_with_config("<input>", source, &AssemblerConfig::new().include_dir(&directory)).unwrap();
+    assert_eq!(program.data, vec![(16, vec![1, 2, 3])]);
+}
+
+#[test]
+fn syntax_errors_are_returned() {
+    let error = bffasm::assemble_file("bad.bffasm", "halt\nreg1 = = 2\n").unwrap_err().to_string();
+    assert!(error.contains("bad.bffasm:2:8"), "{}", error);
+}
+
+#[test]
+fn listings_show_bytes_and_source() {
+    let source = "start:\nreg1 = u 2\nexit reg1\n";
+    let program = bffasm::assemble_file("main.bffasm", source).unwrap();
+    let sources = HashMap::from([("main.bffasm".to_string(), source.to_string())]);
+    assert_eq!(
+        bffasm::listing::listing(&program, &sources),
+        "       start:\n    1  56 01 02 00 00 00 00 00  main.bffasm:2    reg1 = u 2\n    2  62 01 00 00 00 00 00 00  main.bffasm:3    exit reg1\n",
+    );
+}
+
+#[test]
+fn listings_skip_the_source_of_line_0() {
+    let source = "halt\nexit 0\n.loc 1 \"main.bffasm\" 0 0\n.loc 2 \"main.bffasm\" 2 1\n";
+    let program = bffasm::assemble_file("main.bffasm", source).unwrap();
+    let sources = HashMap::from([("main.bffasm".to_string(), source.to_string())]);
+    assert_eq!(
+        bffasm::listing::listing(&program, &sources),
+        "    1  61 00 00 00 00 00 00 00\n    2  63 00 00 00 00 00 00 00  main.bffasm:2    exit 0\n",
+    );
+}
+
+#[test]
+fn listings_are_not_mixed_into_stdout() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("listing_stdout.bffasm");
+    std::fs::write(&path, "halt\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_bffasm")).arg(&path).args(["--listing", "-o", "-"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'--listing' can't be used with '-o -'"));
+}
+
+#[test]
+fn rust_output_builds_the_program() {
+    let program = bffasm::assemble("0 <- \"hi\"\n.data 8\n.bytes 1, 2\n.text\n.entry start\nstart:\nexit 0\n").unwrap();
+    let rust = bffasm::emit::to_rust(&program);
+    assert!(rust.contains("BFFProgram::new((0, 3, 0), vec![\n        Instruction::ExitImmediate(0),\n    ], vec![\n        (0, \"hi\".to_string()),\n    ]);"), "{}", rust);
+    assert!(rust.contains("program.data = vec![\n        (8, vec![1, 2]),\n    ];"), "{}", rust);
+    assert!(rust.contains("program.entry = Some(1);"), "{}", rust);
+    assert!(!rust.contains("Relocation"), "{}", rust);
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+#[test]
+fn data_directives_fill_data_and_bss() {
+    let source = "\
+.data 100
+.bytes 1, 255, -1
+.word 258, -2
+.float 1.5
+.zero 8
+.bytes 7
+.data 300
+.zero 4
+.zero 4
+reg1 <- 100 3
+exit reg1
+";
+    let program = bffasm::assemble(source).unwrap();
+    let mut expected = vec![1, 255, 255, 2, 1, 0, 0, 254, 255, 255, 255];
+    expected.extend_from_slice(&1.5f32.to_bits().to_le_bytes());
+    assert_eq!(program.data, vec![(100, expected), (123, vec![7])]);
+    assert_eq!(program.bss, vec![(115, 8), (300, 8)]);
+
+    let mut program = program;
+    assert_eq!(program.execute(), Ok(1));
+}
+
+#[test]
+fn incbin_reads_next_to_the_source() {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(directory.join("blob.bin"), [0xff, 0xfe, 0, 0x80]).unwrap();
+
+    let source = ".data 16\n.incbin \"blob.bin\"\nhalt\n";
+    let program = bffasm::assemble_file(directory.join("main.bffasm").to_str().unwrap(), source).unwrap();
+    assert_eq!(program.data, vec![(16, vec![0xff, 0xfe, 0, 0x80])]);
+
+    assert!(bffasm::assemble(".data 16\n.incbin \"missing.bin\"\n").is_err());
+}
+
+#[test]
+fn bad_data_directives_are_errors() {
+    assert!(bffasm::assemble(".bytes 1\n").is_err());
+    assert!(bffasm::assemble(".data 0\n.bytes 256\n").is_err());
+    assert!(bffasm::assemble(".data 0\n.word 4294967296\n").is_err());
+    assert!(bffasm::assemble(".version 0.2.0\n.data 0\n.bytes 1\n").is_err());
+}
@@ -0,0 +1,38 @@
+use bffasm::AssemblerConfig;
+use bffcore::engine::bfo_reader::BFOReader;
+use bffcore::engine::debug_info::SourceLocation;
+use bffcore::engine::program::BFFProgram;
+
+#[test]
+fn instructions_map_to_source_lines() {
+    let source = "\
+0 <- \"hi\"
+reg1 = u 3
+loop:
+  reg1 = u reg1 - 1
+  jnz reg1, loop
+exit reg1
+";
+    let program = bffasm::assemble_file("main.bffasm", source).unwrap();
+    let program = BFOReader::read_program(program.to_bfo_bytes()).unwrap();
+
+    assert_eq!(program.debug.lookup(1), Some(&SourceLocation::new("main.bffasm", 2, 1, None)));
+    assert_eq!(program.debug.lookup(2), Some(&SourceLocation::new("main.bffasm", 4, 3, Some("loop"))));
+    assert_eq!(program.debug.lookup(4).unwrap().to_string(), "main.bffasm:6 in loop");
+    assert_eq!(program.debug.lookup(5), None);
+
+    // a division by zero at instruction 2
+    let mut program: BFFProgram = bffasm::assemble_file("div.bffasm", "reg1 = u 1\nreg2 = u reg1 / 0\nhalt\n").unwrap();
+    let trap = program.execute().unwrap_err();
+    assert_eq!(program.debug.lookup(trap.program_counter).unwrap().to_string(), "div.bffasm:2");
+}
+
+#[test]
+fn debug_info_can_be_stripped() {
+    let source = "reg1 = u 1\nexit reg1\n";
+    let program = bffasm::assemble_file("main.bffasm", source).unwrap();
+    let stripped = bffasm::assemble_with_config("main.bffasm", source, &AssemblerConfig::new().strip_debug(true)).unwrap();
+    assert!(stripped.debug.locations.is_empty());
+    assert_eq!(stripped.instructions, program.instructions);
+    assert!(stripped.to_bfo_bytes().len() < program.to_bfo_bytes().len());
+}
@@ -0,0 +1,97 @@
+use bffasm::diagnostic::{AsmError, Diagnostic};
+
+fn errors(source: &str) -> AsmError {
+    bffasm::assemble_file("main.bffasm", source).unwrap_err()
+}
+
+fn positions(error: &AsmError) -> Vec<(usize, usize, &str)> {
+    error.diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.message.as_str())).collect()
+}
+
+#[test]
+fn every_error_in_a_file_is_reported() {
+    let source = "\
+reg1 = u 5
+reg1 = = 2
+jmp nowhere
+reg2 = u - 4
+reg3 = s 3000000000
+exit reg1 +
+";
+    let error = errors(source);
+    assert_eq!(positions(&error), vec![
+        (2, 8, "expected register, symbol address, or operation type"),
+        (3, 5, "undefined symbol 'nowhere'"),
+        (4, 1, "Unsigned negate is not supported"),
+        (5, 10, "'3000000000' doesn't fit in a signed 32 bit value"),
+        (6, 11, "expected a statement"),
+    ]);
+}
+
+#[test]
+fn diagnostics_point_at_the_source() {
+    let error = errors("halt\n\treg1 = reg17\n");
+    assert_eq!(error.diagnostics, vec![Diagnostic {
+        file: "main.bffasm".to_string(),
+        line: 2,
+        column: 9,
+        length: 5,
+        source_line: "\treg1 = reg17".to_string(),
+        message: "'reg17' isn't a register, they go from reg0 to reg16".to_string(),
+        included_from: vec![],
+        notes: vec![],
+    }]);
+    assert_eq!(error.to_string(), "\
+error: 'reg17' isn't a register, they go from reg0 to reg16
+ --> main.bffasm:2:9
+  |
+2 | \treg1 = reg17
+  | \t       ^^^^^");
+}
+
+#[test]
+fn bad_registers_and_numbers_are_errors() {
+    let error = errors("reg300 = u 1\npush reg16\nreg1 = u 4294967296\n.version 0.70000.0\n.data 0\n.bytes 1, 300\n");
+    assert_eq!(positions(&error), vec![
+        (1, 1, "'reg300' isn't a register, they go from reg0 to reg16"),
+        (3, 10, "'4294967296' doesn't fit in an unsigned 32 bit value"),
+        (4, 12, "'70000' doesn't fit in 16 bits"),
+        (6, 11, "'.bytes' value 300 doesn't fit in a byte"),
+    ]);
+}
+
+#[test]
+fn directive_errors_are_located() {
+    let error = errors("halt\n0 <- \"late\"\n.entry start\n.global helper\n.extern print\ncall print\n");
+    assert_eq!(positions(&error), vec![
+        (2, 1, "string stores must be at the top of the file"),
+        (3, 8, "undefined '.entry' label 'start'"),
+        (4, 9, "'.global' symbol 'helper' is never defined"),
+        (6, 6, "'print' is '.extern', assemble as an object and link it with bffld"),
+    ]);
+
+    let error = errors(".version 0.2.0\n.compact\nhalt\n");
+    assert_eq!(positions(&error), vec![(1, 1, "'.entry', '.compact' and data directives need version 0.3 or newer")]);
+}
+
+#[test]
+fn redefined_labels_point_at_the_first_definition() {
+    let error = errors("loop: halt\n.data 16\ncount:\n.word 1\n.text\nloop: jmp loop\ncount: halt\n");
+    assert_eq!(positions(&error), vec![
+        (6, 1, "label 'loop' is already defined"),
+        (7, 1, "label 'count' is already defined"),
+    ]);
+    assert_eq!(error.diagnostics[0].notes, vec!["first defined at main.bffasm:1:1".to_string()]);
+    assert_eq!(error.diagnostics[1].to_string(), "\
+error: label 'count' is already defined
+ --> main.bffasm:7:1
+  |
+7 | count: halt
+  | ^^^^^^
+  = note: first defined at main.bffasm:3:1");
+
+    // a macro's local labels are renamed, so only a clash with the renamed label is an error
+    let error = errors(".macro wait\nloop: jmp loop\n.endm\nwait\nwait_1_loop: halt\n");
+    assert_eq!(positions(&error), vec![(5, 1, "label 'wait_1_loop' is already defined")]);
+    assert_eq!(error.diagnostics[0].notes, vec!["first defined at main.bffasm:4:1".to_string()]);
+}
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+const EXAMPLES: [&str; 3] = ["examples/hello_world.bffasm", "examples/loop_with_calls.bffasm", "examples/fibonacci.bffasm"];
+
+fn repository_file(path: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..").join(path);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("couldn't read '{}': {}", path.display(), e))
+}
+
+fn cells(row: &str) -> Vec<String> {
+    row.trim().trim_matches('|').split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+// the compact encoding table in standards/bfo_file.md, one row per example
+#[test]
+fn compact_size_table_matches_the_examples() {
+    let standard = repository_file("standards/bfo_file.md");
+    let mut expected = vec![];
+    for example in EXAMPLES {
+        let mut program = bffasm::assemble_file(example, &repository_file(example)).unwrap_or_else(|e| panic!("{}", e));
+        program.compact = false;
+        let fixed = program.to_bfo_bytes().len();
+        program.compact = true;
+        let compact = program.to_bfo_bytes().len();
+        let code: usize = program.instructions.iter().map(|instruction| instruction.to_bfo_bytes().len()).sum();
+        let compact_code: usize = program.instructions.iter().map(|instruction| instruction.to_compact_bytes().len()).sum();
+        expected.push(vec![
+            format!("`{}`", example),
+            program.instructions.len().to_string(),
+            code.to_string(),
+            compact_code.to_string(),
+            fixed.to_string(),
+            compact.to_string(),
+        ]);
+    }
+
+    let table: Vec<Vec<String>> = standard.lines()
+        .filter(|line| line.starts_with("| `examples/"))
+        .map(cells)
+        .collect();
+    assert_eq!(table, expected);
+}
+
+#[test]
+fn fibonacci_exits_with_the_eleventh_number() {
+    let mut program = bffasm::assemble(&repository_file("examples/fibonacci.bffasm")).unwrap();
+    assert_eq!(program.execute().unwrap(), 55);
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use bffasm::AssemblerConfig;
+use bffcore::constants::instructions::Instruction;
+
+fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    for (file, source) in files {
+        let path = directory.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    directory
+}
+
+#[test]
+fn includes_are_found_next_to_the_file_and_in_include_dirs() {
+    let directory = directory("include", &[
+        ("main.bffasm", ".include \"lib/sys.bffasm\"\n.include \"consts.bffasm\"\nprint 0, 4\nexit reg1\n"),
+        ("lib/sys.bffasm", "; syscall wrappers\n.include \"write.bffasm\"\n.macro print address, length\npush length\npush address\nwrite\n.endm\n"),
+        ("lib/write.bffasm", ".macro write\npush 0\npush 1\nsyscall\n.endm\n"),
+        ("shared/consts.bffasm", "reg1 = u 3\n"),
+    ]);
+    let main = directory.join("main.bffasm");
+    let source = std::fs::read_to_string(&main).unwrap();
+    assert!(bffasm::assemble_file(main.to_str().unwrap(), &source).is_err());
+
+    let config = AssemblerConfig::new().include_dir(directory.join("shared"));
+    let program = bffasm::assemble_with_config(main.to_str().unwrap(), &source, &config).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(program.instructions, vec![
+        Instruction::MoveImmediate(1, 3),
+        Instruction::PushImmediate(4),
+        Instruction::PushImmediate(0),
+        Instruction::PushImmediate(0),
+        Instruction::PushImmediate(1),
+        Instruction::SystemCall,
+        Instruction::Exit(1),
+    ]);
+    let location = program.debug.lookup(1).unwrap();
+    assert_eq!((location.file.as_str(), location.line), (directory.join("shared/consts.bffasm").to_str().unwrap(), 1));
+}
+
+#[test]
+fn errors_show_the_include_chain() {
+    let directory = directory("include_errors", &[
+        ("main.bffasm", "halt\n.include \"a.bffasm\"\njmp done\n"),
+        ("a.bffasm", "\n.include \"b.bffasm\"\n"),
+        ("b.bffasm", "reg1 = reg99\n.include \"a.bffasm\"\n"),
+    ]);
+    let main = directory.join("main.bffasm");
+    let name = |file: &str| directory.join(file).to_string_lossy().to_string();
+    let error = bffasm::assemble_file(main.to_str().unwrap(), &std::fs::read_to_string(&main).unwrap()).unwrap_err();
+    let messages: Vec<_> = error.diagnostics.iter()
+        .map(|diagnostic| (diagnostic.file.clone(), diagnostic.line, diagnostic.message.as_str(), diagnostic.included_from.clone()))
+        .collect();
+    let chain = vec![(name("a.bffasm"), 2), (name("main.bffasm"), 2)];
+    assert_eq!(messages, vec![
+        (name("b.bffasm"), 1, "'reg99' isn't a register, they go from reg0 to reg16", chain.clone()),
+        (name("b.bffasm"), 2, &*format!("include cycle: {} -> {} -> {}", name("a.bffasm"), name("b.bffasm"), name("a.bffasm")), chain),
+        (name("main.bffasm"), 3, "undefined symbol 'done'", vec![]),
+    ]);
+    assert!(error.to_string().contains(&format!("  = note: included from {}:2\n  = note: included from {}:2", name("a.bffasm"), name("main.bffasm"))));
+}
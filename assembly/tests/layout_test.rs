@@ -0,0 +1,41 @@
+use bffasm::AssemblerConfig;
+use bffasm::preprocessor::substitute_defines;
+use bffcore::constants::instructions::Instruction;
+
+#[test]
+fn comments_go_anywhere_whitespace_does() {
+    let source = "\
+0 <- \"; not a comment\"
+; counts down from 3
+// the counter lives in reg1
+reg1 = u 3 ; trailing
+/* a block comment
+   over two lines */ loop: reg1 = u reg1 - 1 // label and instruction on one line
+jnz reg1, loop /* inline */ ; and another
+halt
+";
+    let program = bffasm::assemble_file("main.bffasm", source).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(program.instructions, vec![
+        Instruction::MoveImmediate(1, 3),
+        Instruction::SubImmediate(1, 1, 1),
+        Instruction::JumpNotZeroImmediate(1, 2),
+        Instruction::Halt,
+    ]);
+    assert_eq!(program.string_table, vec![(0, "; not a comment".to_string())]);
+
+    let location = program.debug.lookup(2).unwrap();
+    assert_eq!((location.line, location.column, location.label.as_deref()), (6, 28, Some("loop")));
+}
+
+#[test]
+fn defines_skip_comments() {
+    let defines = vec![("SIZE".to_string(), "16".to_string())];
+    assert_eq!(
+        substitute_defines("; \"SIZE\nreg1 = u SIZE // SIZE\n/* SIZE\nSIZE */ push SIZE", &defines),
+        "; \"SIZE\nreg1 = u 16 // SIZE\n/* SIZE\nSIZE */ push 16",
+    );
+
+    let config = AssemblerConfig::new().define("SIZE", "16");
+    let program = bffasm::assemble_with_config("<input>", "; the \"size\nreg1 = u SIZE\nexit reg1", &config).unwrap();
+    assert_eq!(program.instructions[0], Instruction::MoveImmediate(1, 16));
+}
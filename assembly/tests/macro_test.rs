@@ -0,0 +1,92 @@
+use bffcore::constants::instructions::Instruction;
+
+const PRINT: &str = "\
+.macro print address, length
+push length ; length of the string
+push address
+push 0 // stdout
+push 1 // write
+syscall
+.endm
+";
+
+#[test]
+fn macros_substitute_their_arguments() {
+    let source = format!("{}\n0 <- \"Hi!\\n\"\nprint 0, 4\nprint 2, 2\nhalt\n", PRINT);
+    let program = bffasm::assemble_file("main.bffasm", &source).unwrap_or_else(|e| panic!("{}", e));
+    let print = |address, length| vec![
+        Instruction::PushImmediate(length),
+        Instruction::PushImmediate(address),
+        Instruction::PushImmediate(0),
+        Instruction::PushImmediate(1),
+        Instruction::SystemCall,
+    ];
+    let mut expected = print(0, 4);
+    expected.extend(print(2, 2));
+    expected.push(Instruction::Halt);
+    assert_eq!(program.instructions, expected);
+
+    // every instruction of an expansion is located at the use of the macro
+    assert_eq!(program.debug.lookup(5).unwrap().line, 10);
+    assert_eq!(program.debug.lookup(6).unwrap().line, 11);
+}
+
+#[test]
+fn labels_are_local_to_each_expansion() {
+    let source = "\
+.macro count_down register, from
+register = u from
+loop: register = u register - 1
+jnz register, loop
+.endm
+.macro twice register
+count_down register, 2
+count_down register, 3
+.endm
+twice reg1
+loop: halt
+jmp loop
+";
+    let program = bffasm::assemble(source).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(program.instructions, vec![
+        Instruction::MoveImmediate(1, 2),
+        Instruction::SubImmediate(1, 1, 1),
+        Instruction::JumpNotZeroImmediate(1, 2),
+        Instruction::MoveImmediate(1, 3),
+        Instruction::SubImmediate(1, 1, 1),
+        Instruction::JumpNotZeroImmediate(1, 5),
+        Instruction::Halt,
+        Instruction::JumpImmediate(7),
+    ]);
+    let mut labels: Vec<&str> = program.symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+    labels.sort();
+    assert_eq!(labels, vec!["count_down_2_loop", "count_down_3_loop", "loop"]);
+
+    // the expansion's labels don't carry over to the instructions after the macro
+    let program = bffasm::assemble(".macro wait\nloop: jmp loop\n.endm\nstart:\nwait\nhalt\n").unwrap();
+    assert_eq!(program.debug.lookup(2).unwrap().label.as_deref(), Some("start"));
+}
+
+#[test]
+fn macro_errors_name_the_macro() {
+    let source = format!("{}\
+.macro forever
+forever
+.endm
+print reg99, 4
+print 0
+nothing 1
+forever
+.macro print a
+.endm
+", PRINT);
+    let error = bffasm::assemble_file("main.bffasm", &source).unwrap_err();
+    let messages: Vec<(usize, &str)> = error.diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.message.as_str())).collect();
+    assert_eq!(messages, vec![
+        (11, "'reg99' isn't a register, they go from reg0 to reg16 (in macro 'print' at line 3)"),
+        (12, "macro 'print' takes 2 arguments, not 1"),
+        (13, "unknown instruction or macro 'nothing'"),
+        (14, "macro 'forever' expands more than 16 levels deep (in macro 'forever' at line 9)"),
+        (15, "macro 'print' is already defined"),
+    ]);
+}
@@ -1,7 +1,8 @@
-use crate::constants::constants::INSTRUCTION_SIZE;
+use crate::constants::constants::{INSTRUCTION_SIZE, REGISTER_COUNT};
 use crate::constants::types::{Address, Bits, Byte, Register};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Nop,
 
@@ -114,19 +115,26 @@ pub enum Instruction {
     Call(Address),
     Return,
 
-    SystemCall
-}
+    SystemCall,
 
-fn get_qb(program: &[u8; INSTRUCTION_SIZE as usize], index: usize) -> (u32, u8) {
-    let address = u32::from_le_bytes([
-        program[index],
-        program[index + 1],
-        program[index + 2],
-        program[index + 3],
-    ]);
-    let reg = program[index + 4];
+    Halt, // stops the program with exit status 0
+    Exit(Register),
+    ExitImmediate(Bits),
+}
 
-    (address, reg)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InstructionClass {
+    Arithmetic,
+    Comparison,
+    Float,
+    Bitwise,
+    Jump,
+    Move,
+    Stack,
+    Memory,
+    Call,
+    SystemCall,
+    Other, // nop, halt and exit
 }
 
 fn get_bd(program: &[u8; INSTRUCTION_SIZE as usize], index: usize) -> (u8, u32) {
@@ -232,6 +240,26 @@ fn get_bbbd(program: &[u8; INSTRUCTION_SIZE as usize], index: usize) -> (u8, u8,
 }
 
 impl Instruction {
+    pub fn opcode(&self) -> u8 {
+        self.to_bfo_bytes()[0]
+    }
+
+    pub fn class(&self) -> InstructionClass {
+        match self.opcode() {
+            1..=12 | 47..=58 | 67..=68 => InstructionClass::Arithmetic,
+            13..=24 | 59..=66 => InstructionClass::Comparison,
+            25..=46 => InstructionClass::Float,
+            69..=80 => InstructionClass::Bitwise,
+            81..=84 => InstructionClass::Jump,
+            85..=86 => InstructionClass::Move,
+            87..=89 => InstructionClass::Stack,
+            90..=93 => InstructionClass::Memory,
+            94..=95 => InstructionClass::Call,
+            96 => InstructionClass::SystemCall,
+            _ => InstructionClass::Other,
+        }
+    }
+
     pub fn to_bfo_bytes(&self) -> [u8; INSTRUCTION_SIZE as usize]{
         match self {
             Instruction::Nop => {
@@ -443,7 +471,7 @@ impl Instruction {
             }
             Instruction::SignedDivModImmediate(dst, dst2, lhs, rhs) => {
                 let rhs = rhs.to_le_bytes();
-                [58, *dst, *dst2, *lhs, rhs[0], rhs[1], rhs[2], 0]
+                [58, *dst, *dst2, *lhs, rhs[0], rhs[1], rhs[2], rhs[3]]
             }
 
             Instruction::SignedGreaterThan(dst, lhs, rhs) => {
@@ -584,11 +612,97 @@ impl Instruction {
             Instruction::SystemCall => {
                 [96, 0, 0, 0, 0, 0, 0, 0]
             }
+
+            Instruction::Halt => {
+                [97, 0, 0, 0, 0, 0, 0, 0]
+            }
+            Instruction::Exit(reg) => {
+                [98, *reg, 0, 0, 0, 0, 0, 0]
+            }
+            Instruction::ExitImmediate(val) => {
+                let val = val.to_le_bytes();
+                [99, val[0], val[1], val[2], val[3], 0, 0, 0]
+            }
+        }
+    }
+
+    // the instruction with its address operand replaced, None if it has none, see engine::section::Relocation
+    pub fn with_address(&self, address: Address) -> Option<Instruction> {
+        let instruction = match *self {
+            Instruction::Call(_) => Instruction::Call(address),
+            Instruction::JumpImmediate(_) => Instruction::JumpImmediate(address),
+            Instruction::JumpNotZeroImmediate(reg, _) => Instruction::JumpNotZeroImmediate(reg, address),
+            Instruction::MoveImmediate(reg, _) => Instruction::MoveImmediate(reg, address),
+            Instruction::PushImmediate(_) => Instruction::PushImmediate(address),
+            Instruction::DirectLoad(reg, _, size) => Instruction::DirectLoad(reg, address, size),
+            Instruction::DirectStore(_, reg, size) => Instruction::DirectStore(address, reg, size),
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    // where the register operands of `opcode` sit in the fixed encoding
+    fn register_offsets(opcode: u8) -> &'static [usize] {
+        match opcode {
+            11 | 35 | 57 => &[1, 2, 3, 4], // divmod
+            12 | 36 | 58 => &[1, 2, 3], // divmod immediate
+            45 | 67 | 69 | 83 | 85 | 90 | 92 => &[1, 2],
+            46 | 68 | 70 | 84 | 86 | 81 | 87 | 89 | 93 | 98 => &[1],
+            91 => &[5], // direct store, the register comes after the address
+            1..=80 if opcode % 2 == 1 => &[1, 2, 3],
+            1..=80 => &[1, 2],
+            _ => &[],
         }
     }
 
-    pub fn from_bfo_bytes(bytes: [u8; INSTRUCTION_SIZE as usize]) -> Instruction {
-        match bytes[0] {
+    pub fn registers(&self) -> Vec<Register> {
+        let bytes = self.to_bfo_bytes();
+        Self::register_offsets(bytes[0]).iter().map(|&offset| bytes[offset]).collect()
+    }
+
+    // the first register operand that isn't below REGISTER_COUNT, decoders reject these so a program
+    // read from a file can't name a register the vm doesn't have
+    pub fn invalid_register(&self) -> Option<Register> {
+        self.registers().into_iter().find(|&register| register as usize >= REGISTER_COUNT)
+    }
+
+    // the compact encoding keeps the opcode and its operands and drops the fixed encoding's padding,
+    // None for an unknown opcode
+    pub fn compact_size(opcode: u8) -> Option<usize> {
+        let size = match opcode {
+            0 | 95..=97 => 1, // no operands
+            11 | 35 | 57 => 5, // divmod, 4 registers
+            12 | 36 | 58 => 8, // divmod immediate
+            45 | 67 | 69 | 83 | 85 => 3, // 2 registers
+            46 | 68 | 70 | 84 | 86 => 6, // a register and an immediate
+            81 | 87 | 89 | 98 => 2, // a register
+            82 | 88 | 94 | 99 => 5, // an immediate or address
+            90 | 92 => 4, // 2 registers and a size
+            91 | 93 => 7, // a register, an address and a size
+            1..=80 if opcode % 2 == 1 => 4, // 3 registers
+            1..=80 => 7, // 2 registers and an immediate
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let bytes = self.to_bfo_bytes();
+        bytes[..Self::compact_size(bytes[0]).unwrap()].to_vec()
+    }
+
+    // the instruction at the start of `bytes` and how many bytes it took,
+    // None for an unknown opcode or when `bytes` ends partway through the instruction
+    pub fn from_compact_bytes(bytes: &[u8]) -> Option<(Instruction, usize)> {
+        let size = Self::compact_size(*bytes.first()?)?;
+        let mut fixed = [0; INSTRUCTION_SIZE as usize];
+        fixed[..size].copy_from_slice(bytes.get(..size)?);
+        Some((Self::from_bfo_bytes(fixed)?, size))
+    }
+
+    // None for an unknown opcode
+    pub fn from_bfo_bytes(bytes: [u8; INSTRUCTION_SIZE as usize]) -> Option<Instruction> {
+        let instruction = match bytes[0] {
             0 => { // Instruction::Nop
                 Instruction::Nop
             }
@@ -985,7 +1099,20 @@ impl Instruction {
             96 => { // Instruction::SystemCall
                 Instruction::SystemCall
             }
-            opcode => unimplemented!("Unknown instruction: {}", opcode),
-        }
+
+            97 => { // Instruction::Halt
+                Instruction::Halt
+            }
+            98 => { // Instruction::Exit
+                let reg = get_b(&bytes, 1);
+                Instruction::Exit(reg)
+            }
+            99 => { // Instruction::ExitImmediate
+                let bits = get_d(&bytes, 1);
+                Instruction::ExitImmediate(bits)
+            }
+            _ => return None,
+        };
+        Some(instruction)
     }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod constants;
 pub mod instructions;
 pub mod types;
@@ -1,7 +1,10 @@
-use crate::constants::constants::INSTRUCTION_SIZE;
+use std::fmt;
+use crate::constants::constants::{INSTRUCTION_SIZE, SECTIONED_VERSION, VERSION};
 use crate::constants::instructions::Instruction;
-use crate::constants::types::{Register};
+use crate::constants::types::{Address, Byte};
+use crate::engine::debug_info::{DebugInfo, SourceLocation};
 use crate::engine::program::BFFProgram;
+use crate::engine::section::{Relocation, SectionKind, Symbol, SymbolBinding, SymbolKind, COMPACT_CODE, KNOWN_FLAGS, OBJECT, SIGNATURE_SIZE};
 
 
 macro_rules! array_from_8_elements_in_array {
@@ -30,23 +33,142 @@ macro_rules! array_from_4_elements_in_array {
     };
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum BfoError {
+    TruncatedHeader(usize), // file length
+    TruncatedStringEntry { entry: u64, offset: usize },
+    TruncatedSection { kind: u32, offset: usize },
+    TrailingPartialInstruction { offset: usize, length: usize },
+    UnknownOpcode { offset: usize, opcode: u8 },
+    InvalidRegister { offset: usize, register: u8 },
+    VersionMismatch { expected: (u16, u16, u16), found: (u16, u16, u16) },
+    UnsupportedVersion((u16, u16, u16)), // older than any version we have a decoder for
+    UnknownFlags(u16),
+    ChecksumMismatch { expected: u32, found: u32 },
+    MissingChecksum,
+    UncheckedSection { kind: u32, offset: usize }, // a section after the checksum, which only covers what's before it
+    MissingSignature,
+    BadSignature,
+    Io(String),
+}
+
+impl fmt::Display for BfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BfoError::TruncatedHeader(length) => write!(f, "truncated header, the file is only {} bytes", length),
+            BfoError::TruncatedStringEntry { entry, offset } => write!(f, "string table entry {} at offset {} runs past the end of the file", entry, offset),
+            BfoError::TruncatedSection { kind, offset } => write!(f, "section of kind {} at offset {} runs past its end", kind, offset),
+            BfoError::TrailingPartialInstruction { offset, length } => write!(f, "trailing partial instruction at offset {}, {} of {} bytes", offset, length, INSTRUCTION_SIZE),
+            BfoError::UnknownOpcode { offset, opcode } => write!(f, "unknown opcode {} at offset {}", opcode, offset),
+            BfoError::InvalidRegister { offset, register } => write!(f, "invalid register {} in the instruction at offset {}", register, offset),
+            BfoError::VersionMismatch { expected, found } => write!(f, "version mismatch, expected {:?} or older, got {:?}", expected, found),
+            BfoError::UnsupportedVersion(version) => write!(f, "unsupported version {:?}", version),
+            BfoError::UnknownFlags(flags) => write!(f, "unknown header flags {:#06x}", flags),
+            BfoError::ChecksumMismatch { expected, found } => write!(f, "checksum mismatch, expected {:#010x}, got {:#010x}, the file is corrupt or was changed", expected, found),
+            BfoError::MissingChecksum => write!(f, "the file has no checksum section, it was cut short or not written by a bff tool"),
+            BfoError::UncheckedSection { kind, offset } => write!(f, "section of kind {} at offset {} isn't covered by the checksum", kind, offset),
+            BfoError::MissingSignature => write!(f, "the file isn't signed"),
+            BfoError::BadSignature => write!(f, "the signature doesn't match the key, or the file was changed after signing"),
+            BfoError::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for BfoError {}
+
+const VERSION_SIZE: usize = 8; // version and reserved bytes
+const STRING_HEADER_SIZE: usize = 16; // version, reserved bytes and the string count
+const DIRECTORY_ENTRY_SIZE: usize = 12;
+
+// bounds checked little endian reads for the sectioned format
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_bytes(bytes: &[u8], offset: usize, length: usize) -> Option<&[u8]> {
+    bytes.get(offset..offset.checked_add(length)?)
+}
+
 pub struct BFOReader {}
 
 
 impl BFOReader {
-    pub fn read_program(program: Vec<u8>) -> BFFProgram {
-        let mut index = 16;
+    pub fn read_program(program: Vec<u8>) -> Result<BFFProgram, BfoError> {
+        Self::decode(&program)
+    }
+
+    // like read_program, but the file has to be signed by `public_key`
+    #[cfg(feature = "signing")]
+    pub fn read_signed_program(program: Vec<u8>, public_key: &ed25519_dalek::VerifyingKey) -> Result<BFFProgram, BfoError> {
+        let bff_program = Self::decode(&program)?;
+        if !bff_program.is_sectioned() {
+            return Err(BfoError::MissingSignature);
+        }
+
+        let directory = Self::read_directory(&program)?;
+        let (_, offset, _) = *directory.iter()
+            .find(|(kind, _, _)| *kind == SectionKind::Signature as u32)
+            .ok_or(BfoError::MissingSignature)?;
+        // decode checked that the section is in bounds
+        let section = &program[offset..offset + SIGNATURE_SIZE];
+        if section[..32] != public_key.as_bytes()[..] {
+            return Err(BfoError::BadSignature);
+        }
+
+        let signature = ed25519_dalek::Signature::from_slice(&section[32..]).map_err(|_| BfoError::BadSignature)?;
+        public_key.verify_strict(&program[..Self::payload_end(&directory, program.len())], &signature)
+            .map_err(|_| BfoError::BadSignature)?;
+        Ok(bff_program)
+    }
+
+    fn decode(program: &[u8]) -> Result<BFFProgram, BfoError> {
+        if program.len() < VERSION_SIZE {
+            return Err(BfoError::TruncatedHeader(program.len()));
+        }
         let version_major = u16::from_le_bytes([program[0], program[1]]);
         let version_minor = u16::from_le_bytes([program[2], program[3]]);
         let version_incremental = u16::from_le_bytes([program[4], program[5]]);
         let version = (version_major, version_minor, version_incremental);
 
+        // every decoder gives back the same kind of program, whatever the file could hold is filled in
+        match version {
+            _ if version > VERSION => Err(BfoError::VersionMismatch { expected: VERSION, found: version }),
+            _ if version >= SECTIONED_VERSION => Self::read_sections(version, program),
+            (0, 2, _) => Self::read_v0_2(version, program),
+            (0, 1, _) => Self::read_v0_1(version, program),
+            _ => Err(BfoError::UnsupportedVersion(version)),
+        }
+    }
+
+    // 0.1: instructions right after the version, up to the end of the file
+    fn read_v0_1(version: (u16, u16, u16), program: &[u8]) -> Result<BFFProgram, BfoError> {
+        let instructions = Self::read_instructions(program, VERSION_SIZE, program.len())?;
+        Ok(BFFProgram::new(version, instructions, vec![]))
+    }
+
+    // 0.2: a string count, the string table and then instructions up to the end of the file
+    fn read_v0_2(version: (u16, u16, u16), program: &[u8]) -> Result<BFFProgram, BfoError> {
+        if program.len() < STRING_HEADER_SIZE {
+            return Err(BfoError::TruncatedHeader(program.len()));
+        }
+        let mut index = STRING_HEADER_SIZE;
+
         let num_strings = u64::from_le_bytes(
             array_from_8_elements_in_array!(program, 8)
         );
 
         let mut string_table = vec![];
-        for _ in 0..num_strings {
+        for entry in 0..num_strings {
+            let truncated = BfoError::TruncatedStringEntry { entry, offset: index };
+            if index + 8 > program.len() {
+                return Err(truncated);
+            }
             let string_length = u32::from_le_bytes(
                 array_from_4_elements_in_array!(program, index)
             );
@@ -56,48 +178,245 @@ impl BFOReader {
             );
 
             index += 8;
-            let mut string = String::new();
-            for _ in 0..string_length {
-                string.push(program[index] as char);
-                index += 1;
+            if index + string_length as usize > program.len() {
+                return Err(truncated);
             }
+            // to_bfo_bytes writes utf-8
+            let string = String::from_utf8_lossy(&program[index..index + string_length as usize]).into_owned();
+            index += string_length as usize;
             string_table.push((string_location, string));
         }
 
+        let instructions = Self::read_instructions(program, index, program.len())?;
+        Ok(BFFProgram::new(version, instructions, string_table))
+    }
 
-        if version != crate::constants::constants::VERSION {
-            eprintln!("Version Mismatch, expected {:?}, got {:?}", crate::constants::constants::VERSION, version);
+    // 0.3+: a section directory, see standards/bfo_file.md
+    fn read_sections(version: (u16, u16, u16), program: &[u8]) -> Result<BFFProgram, BfoError> {
+        let flags = read_u16(program, 6).unwrap();
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(BfoError::UnknownFlags(flags & !KNOWN_FLAGS));
+        }
+        let directory = Self::read_directory(program)?;
+        let payload_end = Self::payload_end(&directory, program.len());
+        if !directory.iter().any(|(kind, _, _)| *kind == SectionKind::Checksum as u32) {
+            return Err(BfoError::MissingChecksum);
         }
 
-        let mut instructions = vec![];
-        if index + INSTRUCTION_SIZE as usize > program.len() {
-            panic!("WELL SHT") // place holder error
+        let mut bff_program = BFFProgram::new(version, vec![], vec![]);
+        bff_program.compact = flags & COMPACT_CODE != 0;
+        bff_program.object = flags & OBJECT != 0;
+        for (kind, offset, length) in directory {
+            let truncated = BfoError::TruncatedSection { kind, offset };
+            let section = read_bytes(program, offset, length).ok_or(truncated.clone())?;
+
+            let trailer = kind == SectionKind::Checksum as u32 || kind == SectionKind::Signature as u32;
+            if !trailer && offset + length > payload_end {
+                return Err(BfoError::UncheckedSection { kind, offset });
+            }
+
+            match SectionKind::from_u32(kind) {
+                Some(SectionKind::Code) if bff_program.compact => {
+                    bff_program.instructions = Self::read_compact_instructions(program, offset, offset + length)?;
+                }
+                Some(SectionKind::Code) => {
+                    bff_program.instructions = Self::read_instructions(program, offset, offset + length)?;
+                }
+                Some(SectionKind::ReadOnlyData) => {
+                    for (location, bytes) in Self::read_data(section).ok_or(truncated)? {
+                        bff_program.string_table.push((location, String::from_utf8_lossy(&bytes).into_owned()));
+                    }
+                }
+                Some(SectionKind::Data) => {
+                    bff_program.data = Self::read_data(section).ok_or(truncated)?;
+                }
+                Some(SectionKind::Bss) => {
+                    let count = read_u32(section, 0).ok_or(truncated.clone())?;
+                    let mut index = 4;
+                    for _ in 0..count {
+                        let location = read_u32(section, index).ok_or(truncated.clone())?;
+                        let size = read_u32(section, index + 4).ok_or(truncated.clone())?;
+                        bff_program.bss.push((location, size));
+                        index += 8;
+                    }
+                }
+                Some(SectionKind::Entry) => {
+                    bff_program.entry = Some(read_u32(section, 0).ok_or(truncated)?);
+                }
+                Some(SectionKind::Symbols) => {
+                    bff_program.symbols = Self::read_symbols(section).ok_or(truncated)?;
+                }
+                Some(SectionKind::Relocations) => {
+                    bff_program.relocations = Self::read_relocations(section).ok_or(truncated)?;
+                }
+                Some(SectionKind::Debug) => {
+                    bff_program.debug = Self::read_debug_info(section).ok_or(truncated)?;
+                }
+                Some(SectionKind::Checksum) => {
+                    let expected = read_u32(section, 0).ok_or(truncated)?;
+                    let found = crc32fast::hash(&program[..payload_end]);
+                    if expected != found {
+                        return Err(BfoError::ChecksumMismatch { expected, found });
+                    }
+                }
+                // checked by read_signed_program, only its size matters here
+                Some(SectionKind::Signature) if length != SIGNATURE_SIZE => {
+                    return Err(truncated);
+                }
+                Some(SectionKind::Signature) => {}
+                None => {} // from a newer writer, safe to skip
+            }
         }
+        Ok(bff_program)
+    }
 
-        while index < program.len() {
-            instructions.push(
-                Instruction::from_bfo_bytes(
-                    array_from_8_elements_in_array!(program, index)
-                )
-            );
-            index += INSTRUCTION_SIZE as usize;
+    // (kind, offset, length) per entry
+    fn read_directory(program: &[u8]) -> Result<Vec<(u32, usize, usize)>, BfoError> {
+        let section_count = read_u32(program, VERSION_SIZE).ok_or(BfoError::TruncatedHeader(program.len()))? as usize;
+        let directory_end = section_count.checked_mul(DIRECTORY_ENTRY_SIZE)
+            .and_then(|size| size.checked_add(VERSION_SIZE + 4))
+            .filter(|end| *end <= program.len())
+            .ok_or(BfoError::TruncatedHeader(program.len()))?;
+
+        Ok((VERSION_SIZE + 4..directory_end).step_by(DIRECTORY_ENTRY_SIZE)
+            .map(|entry| (
+                read_u32(program, entry).unwrap(),
+                read_u32(program, entry + 4).unwrap() as usize,
+                read_u32(program, entry + 8).unwrap() as usize,
+            ))
+            .collect())
+    }
+
+    // where the checksum and signature start, everything before them is what they cover
+    fn payload_end(directory: &[(u32, usize, usize)], file_length: usize) -> usize {
+        directory.iter()
+            .filter(|(kind, _, _)| *kind == SectionKind::Checksum as u32 || *kind == SectionKind::Signature as u32)
+            .map(|(_, offset, _)| *offset)
+            .min()
+            .unwrap_or(file_length)
+    }
+
+    fn read_instructions(program: &[u8], start: usize, end: usize) -> Result<Vec<Instruction>, BfoError> {
+        let trailing = (end - start) % INSTRUCTION_SIZE as usize;
+        if trailing != 0 {
+            return Err(BfoError::TrailingPartialInstruction { offset: end - trailing, length: trailing });
         }
 
-        BFFProgram {
-            version,
-            instructions,
-            string_table,
+        let mut index = start;
+        let mut instructions = vec![];
+        while index < end {
+            let instruction = Instruction::from_bfo_bytes(
+                array_from_8_elements_in_array!(program, index)
+            ).ok_or(BfoError::UnknownOpcode { offset: index, opcode: program[index] })?;
+            if let Some(register) = instruction.invalid_register() {
+                return Err(BfoError::InvalidRegister { offset: index, register });
+            }
+            instructions.push(instruction);
+            index += INSTRUCTION_SIZE as usize;
         }
+        Ok(instructions)
     }
 
-    pub fn read_file_from_path(path: String) -> BFFProgram {
-        let program = match std::fs::read(path) {
-            Ok(contents) => contents,
-            Err(err) => {
-                eprintln!("Couldn't Read The Program");
-                panic!("{}", err);
+    fn read_compact_instructions(program: &[u8], start: usize, end: usize) -> Result<Vec<Instruction>, BfoError> {
+        let mut index = start;
+        let mut instructions = vec![];
+        while index < end {
+            let opcode = program[index];
+            if Instruction::compact_size(opcode).is_none() {
+                return Err(BfoError::UnknownOpcode { offset: index, opcode });
+            }
+            let (instruction, size) = Instruction::from_compact_bytes(&program[index..end])
+                .ok_or(BfoError::TrailingPartialInstruction { offset: index, length: end - index })?;
+            if let Some(register) = instruction.invalid_register() {
+                return Err(BfoError::InvalidRegister { offset: index, register });
             }
-        };
+            instructions.push(instruction);
+            index += size;
+        }
+        Ok(instructions)
+    }
+
+    fn read_data(section: &[u8]) -> Option<Vec<(Address, Vec<Byte>)>> {
+        let count = read_u32(section, 0)?;
+        let mut index = 4;
+        let mut entries = vec![];
+        for _ in 0..count {
+            let location = read_u32(section, index)?;
+            let length = read_u32(section, index + 4)? as usize;
+            entries.push((location, read_bytes(section, index + 8, length)?.to_vec()));
+            index += 8 + length;
+        }
+        Some(entries)
+    }
+
+    fn read_symbols(section: &[u8]) -> Option<Vec<Symbol>> {
+        let count = read_u32(section, 0)?;
+        let mut index = 4;
+        let mut symbols = vec![];
+        for _ in 0..count {
+            let value = read_u32(section, index)?;
+            let kind = match *section.get(index + 4)? {
+                0 => SymbolKind::Code,
+                _ => SymbolKind::Data,
+            };
+            let binding = match *section.get(index + 5)? {
+                0 => SymbolBinding::Local,
+                2 => SymbolBinding::Extern,
+                _ => SymbolBinding::Global,
+            };
+            let length = read_u16(section, index + 6)? as usize;
+            let name = String::from_utf8_lossy(read_bytes(section, index + 8, length)?).into_owned();
+            symbols.push(Symbol { name, value, kind, binding });
+            index += 8 + length;
+        }
+        Some(symbols)
+    }
+
+    fn read_relocations(section: &[u8]) -> Option<Vec<Relocation>> {
+        let count = read_u32(section, 0)?;
+        let mut index = 4;
+        let mut relocations = vec![];
+        for _ in 0..count {
+            let instruction = read_u32(section, index)?;
+            let length = read_u16(section, index + 4)? as usize;
+            let symbol = String::from_utf8_lossy(read_bytes(section, index + 6, length)?).into_owned();
+            relocations.push(Relocation { index: instruction, symbol });
+            index += 6 + length;
+        }
+        Some(relocations)
+    }
+
+    fn read_debug_info(section: &[u8]) -> Option<DebugInfo> {
+        let name_count = read_u32(section, 0)?;
+        let mut index = 4;
+        let mut names = vec![];
+        for _ in 0..name_count {
+            let length = read_u16(section, index)? as usize;
+            names.push(String::from_utf8_lossy(read_bytes(section, index + 2, length)?).into_owned());
+            index += 2 + length;
+        }
+
+        let count = read_u32(section, index)?;
+        index += 4;
+        let mut debug_info = DebugInfo::new();
+        for _ in 0..count {
+            let instruction = read_u32(section, index)?;
+            let file = names.get(read_u32(section, index + 4)? as usize)?;
+            let line = read_u32(section, index + 8)?;
+            let column = read_u32(section, index + 12)?;
+            let label = match read_u32(section, index + 16)? {
+                u32::MAX => None,
+                label => Some(names.get(label as usize)?.as_str()),
+            };
+            debug_info.insert(instruction, SourceLocation::new(file, line, column, label));
+            index += 20;
+        }
+        Some(debug_info)
+    }
+
+    pub fn read_file_from_path(path: String) -> Result<BFFProgram, BfoError> {
+        let program = std::fs::read(&path)
+            .map_err(|err| BfoError::Io(format!("couldn't read '{}': {}", path, err)))?;
 
         Self::read_program(program)
     }
@@ -0,0 +1,54 @@
+use crate::constants::constants::{BASE_CALL_DEPTH, BASE_MEMORY_SIZE, BASE_STACK_SIZE, REGISTER_COUNT};
+use crate::constants::types::{Bits, Register};
+use crate::engine::virtual_machine::VirtualMachine;
+
+// sizes and initial state for a VirtualMachine, i.e. VmConfig::new().memory_size(4096).build()
+#[derive(Clone, Debug, PartialEq)]
+pub struct VmConfig {
+    pub memory_size: usize, // in bytes
+    pub stack_size: usize, // in stack words
+    pub max_call_depth: usize,
+    pub registers: [Bits; REGISTER_COUNT],
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self {
+            memory_size: BASE_MEMORY_SIZE,
+            stack_size: BASE_STACK_SIZE,
+            max_call_depth: BASE_CALL_DEPTH,
+            registers: [0; REGISTER_COUNT],
+        }
+    }
+}
+
+impl VmConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn memory_size(mut self, memory_size: usize) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = stack_size;
+        self
+    }
+
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    // panics if `register` isn't below REGISTER_COUNT
+    pub fn register(mut self, register: Register, value: Bits) -> Self {
+        self.registers[register as usize] = value;
+        self
+    }
+
+    pub fn build(&self) -> VirtualMachine {
+        VirtualMachine::with_config(self)
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+use crate::constants::types::Address;
+
+// where an instruction came from in the assembly source
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub label: Option<String>, // the closest label above the instruction
+}
+
+impl SourceLocation {
+    pub fn new(file: &str, line: u32, column: u32, label: Option<&str>) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+            column,
+            label: label.map(|label| label.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if let Some(label) = &self.label {
+            write!(f, " in {}", label)?;
+        }
+        Ok(())
+    }
+}
+
+// the contents of a bfo debug section, instruction indexes (1 is the first instruction) to source locations
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DebugInfo {
+    pub locations: Vec<(Address, SourceLocation)>, // sorted by instruction index
+}
+
+impl DebugInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    pub fn insert(&mut self, index: Address, location: SourceLocation) {
+        match self.locations.binary_search_by_key(&index, |(index, _)| *index) {
+            Ok(position) => self.locations[position].1 = location,
+            Err(position) => self.locations.insert(position, (index, location)),
+        }
+    }
+
+    // takes a program counter, so a trap or the debugger can name the source line
+    pub fn lookup(&self, index: usize) -> Option<&SourceLocation> {
+        self.locations.binary_search_by_key(&index, |(index, _)| *index as usize)
+            .ok()
+            .map(|position| &self.locations[position].1)
+    }
+}
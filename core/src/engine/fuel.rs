@@ -0,0 +1,57 @@
+use crate::constants::instructions::InstructionClass;
+
+// how much fuel each class of instruction burns, everything costs 1 by default so fuel == steps
+#[derive(Clone, Debug)]
+pub struct FuelCosts {
+    pub arithmetic: u64,
+    pub comparison: u64,
+    pub float: u64,
+    pub bitwise: u64,
+    pub jump: u64,
+    pub move_: u64,
+    pub stack: u64,
+    pub memory: u64,
+    pub call: u64,
+    pub system_call: u64,
+    pub other: u64,
+}
+
+impl Default for FuelCosts {
+    fn default() -> Self {
+        Self::uniform(1)
+    }
+}
+
+impl FuelCosts {
+    pub fn uniform(cost: u64) -> Self {
+        Self {
+            arithmetic: cost,
+            comparison: cost,
+            float: cost,
+            bitwise: cost,
+            jump: cost,
+            move_: cost,
+            stack: cost,
+            memory: cost,
+            call: cost,
+            system_call: cost,
+            other: cost,
+        }
+    }
+
+    pub fn cost(&self, class: InstructionClass) -> u64 {
+        match class {
+            InstructionClass::Arithmetic => self.arithmetic,
+            InstructionClass::Comparison => self.comparison,
+            InstructionClass::Float => self.float,
+            InstructionClass::Bitwise => self.bitwise,
+            InstructionClass::Jump => self.jump,
+            InstructionClass::Move => self.move_,
+            InstructionClass::Stack => self.stack,
+            InstructionClass::Memory => self.memory,
+            InstructionClass::Call => self.call,
+            InstructionClass::SystemCall => self.system_call,
+            InstructionClass::Other => self.other,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use serde_json::Value;
+use crate::engine::program::BFFProgram;
+
+impl BFFProgram {
+    // see `write_readable`, fields are in declaration order
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        write_readable(&mut json, &serde_json::to_value(self).unwrap(), 0);
+        json.push('\n');
+        json
+    }
+
+    // fields with a default can be left out, see BFFProgram
+    pub fn from_json(json: &str) -> Result<BFFProgram, String> {
+        let program: BFFProgram = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        for (index, instruction) in program.instructions.iter().enumerate() {
+            if let Some(register) = instruction.invalid_register() {
+                return Err(format!("invalid register {} in instruction {}", register, index));
+            }
+        }
+        Ok(program)
+    }
+}
+
+// indented like serde_json's pretty printer, except that arrays of plain values stay on one line and
+// arrays of anything else get one compact element per line, so every instruction, symbol and data entry
+// is a single line in a diff
+fn write_readable(output: &mut String, value: &Value, indent: usize) {
+    let padding = "  ".repeat(indent + 1);
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            output.push_str("{\n");
+            for (index, (name, field)) in fields.iter().enumerate() {
+                output.push_str(&format!("{}{}: ", padding, Value::String(name.clone())));
+                write_readable(output, field, indent + 1);
+                output.push_str(if index + 1 < fields.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&format!("{}}}", "  ".repeat(indent)));
+        }
+        Value::Array(elements) if elements.iter().any(|element| element.is_array() || element.is_object()) => {
+            output.push_str("[\n");
+            for (index, element) in elements.iter().enumerate() {
+                output.push_str(&format!("{}{}", padding, element));
+                output.push_str(if index + 1 < elements.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&format!("{}]", "  ".repeat(indent)));
+        }
+        value => output.push_str(&value.to_string()),
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+use crate::constants::types::Address;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Permissions {
+    pub const NONE: Permissions = Permissions { read: false, write: false };
+    pub const READ_ONLY: Permissions = Permissions { read: true, write: false };
+    pub const READ_WRITE: Permissions = Permissions { read: true, write: true };
+
+    pub fn allows(&self, access: Access) -> bool {
+        match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        }
+    }
+}
+
+// a span of guest memory, when regions overlap the one mapped last wins
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryRegion {
+    pub start: Address,
+    pub length: usize,
+    pub permissions: Permissions,
+}
+
+impl MemoryRegion {
+    pub fn new(start: Address, length: usize, permissions: Permissions) -> Self {
+        Self {
+            start,
+            length,
+            permissions,
+        }
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.start as usize && address < self.start as usize + self.length
+    }
+
+    pub fn end(&self) -> usize {
+        self.start as usize + self.length
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+        }
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.read { "r" } else { "-" }, if self.write { "w" } else { "-" })
+    }
+}
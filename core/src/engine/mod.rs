@@ -1,3 +1,14 @@
 pub mod virtual_machine;
 pub mod bfo_reader;
-pub mod program;
\ No newline at end of file
+pub mod program;
+pub mod trap;
+pub mod fuel;
+pub mod trace;
+pub mod syscalls;
+pub mod config;
+pub mod memory;
+pub mod snapshot;
+pub mod section;
+pub mod debug_info;
+#[cfg(feature = "serde")]
+pub mod json;
@@ -1,9 +1,37 @@
+use crate::constants::constants::{SECTIONED_VERSION, VERSION};
 use crate::constants::instructions::Instruction;
+use crate::constants::types::{Address, Bits, Byte};
+use crate::engine::debug_info::DebugInfo;
+use crate::engine::memory::Permissions;
+use crate::engine::section::{Relocation, SectionKind, Symbol, CHECKSUM_SIZE, COMPACT_CODE, OBJECT, SIGNATURE_SIZE};
+use crate::engine::trap::{TrapReason, VmTrap};
+use crate::engine::virtual_machine::VirtualMachine;
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BFFProgram {
     pub version: (u16, u16, u16),
     pub instructions: Vec<Instruction>,
-    pub string_table: Vec<(u32, String)>,
+    // anything with a default can be left out of json
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub string_table: Vec<(u32, String)>, // read-only data
+    // everything below needs a sectioned (0.3+) file, older versions drop it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub data: Vec<(Address, Vec<Byte>)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bss: Vec<(Address, u32)>, // zeroed writable memory, address and size
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub entry: Option<Address>, // instruction index to start at, like a label
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symbols: Vec<Symbol>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub debug: DebugInfo,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compact: bool, // write the code section with Instruction::to_compact_bytes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub object: bool, // relocatable, see bffld
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub relocations: Vec<Relocation>,
 }
 
 
@@ -13,37 +41,252 @@ impl BFFProgram {
             version,
             instructions,
             string_table,
+            data: vec![],
+            bss: vec![],
+            entry: None,
+            symbols: vec![],
+            debug: DebugInfo::new(),
+            compact: false,
+            object: false,
+            relocations: vec![],
         }
     }
 
-    pub fn execute(&mut self) {
-        let mut vm = crate::engine::virtual_machine::VirtualMachine::new();
+    pub fn is_sectioned(&self) -> bool {
+        self.version >= SECTIONED_VERSION
+    }
+
+    // moves the program to the newest format, nothing is lost since every older format is a subset of it
+    pub fn upgrade(&mut self) {
+        self.version = VERSION;
+    }
+
+    // fails if the data doesn't fit in the vm's memory, strings are mapped read-only
+    pub fn load_into(&self, vm: &mut VirtualMachine) -> Result<(), TrapReason> {
         vm.load_program(self.instructions.clone());
+        for (location, bytes) in &self.data {
+            vm.store_bytes(*location, bytes)?;
+            vm.map_region(*location, bytes.len(), Permissions::READ_WRITE)?;
+        }
+        for (location, size) in &self.bss {
+            vm.store_bytes(*location, &vec![0; *size as usize])?;
+            vm.map_region(*location, *size as usize, Permissions::READ_WRITE)?;
+        }
         for (location, string) in &self.string_table {
-            vm.store_string(*location, &*string);
+            vm.store_string(*location, string)?;
+            if !string.is_empty() {
+                vm.map_region(*location, string.len(), Permissions::READ_ONLY)?;
+            }
         }
+        if let Some(entry) = self.entry {
+            vm.program_counter = entry as usize;
+        }
+        Ok(())
+    }
 
-        vm.execute_instruction_list();
+    pub fn execute(&mut self) -> Result<Bits, VmTrap> {
+        let mut vm = VirtualMachine::new();
+        self.load_into(&mut vm).map_err(|reason| VmTrap::new(0, Instruction::Nop, reason))?;
+
+        vm.execute_instruction_list()
     }
 
+    // the layout follows `version`, see standards/bfo_file.md
     pub fn to_bfo_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.version_bytes();
+
+        if self.is_sectioned() {
+            self.write_sections(&mut bytes, false);
+            return bytes;
+        }
+
+        // 0.1 has no string table
+        if self.version >= (0, 2, 0) {
+            bytes.extend_from_slice(&(self.string_table.len() as u64).to_le_bytes());
+
+            for (location, string) in &self.string_table {
+                bytes.extend_from_slice(&(string.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(&location.to_le_bytes());
+
+                bytes.extend_from_slice(string.as_bytes());
+            }
+        }
+
+        for instruction in &self.instructions {
+            bytes.extend_from_slice(&instruction.to_bfo_bytes());
+        }
+        bytes
+    }
+
+    // like to_bfo_bytes with a signature section, only sectioned (0.3+) files can be signed
+    #[cfg(feature = "signing")]
+    pub fn to_signed_bfo_bytes(&self, key: &ed25519_dalek::SigningKey) -> Option<Vec<u8>> {
+        use ed25519_dalek::Signer;
+
+        if !self.is_sectioned() {
+            return None;
+        }
+        let mut bytes = self.version_bytes();
+        self.write_sections(&mut bytes, true);
+
+        let payload_end = bytes.len() - CHECKSUM_SIZE - SIGNATURE_SIZE;
+        let signature = key.sign(&bytes[..payload_end]);
+        let signature_start = bytes.len() - SIGNATURE_SIZE;
+        bytes[signature_start..signature_start + 32].copy_from_slice(key.verifying_key().as_bytes());
+        bytes[signature_start + 32..].copy_from_slice(&signature.to_bytes());
+        Some(bytes)
+    }
+
+    // the 8 bytes every version starts with, the last two are flags from 0.3 on and reserved before that
+    fn version_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.compact && self.is_sectioned() {
+            flags |= COMPACT_CODE;
+        }
+        if self.object && self.is_sectioned() {
+            flags |= OBJECT;
+        }
+
         let mut bytes = vec![];
         bytes.extend_from_slice(&self.version.0.to_le_bytes());
         bytes.extend_from_slice(&self.version.1.to_le_bytes());
         bytes.extend_from_slice(&self.version.2.to_le_bytes());
-        bytes.extend_from_slice(&[0, 0]);
-        bytes.extend_from_slice(&(self.string_table.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes
+    }
 
-        for (location, string) in &self.string_table {
-            bytes.extend_from_slice(&(string.len() as u32).to_le_bytes());
-            bytes.extend_from_slice(&location.to_le_bytes());
+    // u32 section count, then a directory of (u32 kind, u32 offset, u32 length) and the sections themselves,
+    // empty sections are left out except for code. the checksum, and the signature space if `signed`, go last
+    fn write_sections(&self, bytes: &mut Vec<u8>, signed: bool) {
+        let mut sections: Vec<(SectionKind, Vec<u8>)> = vec![];
 
-            bytes.extend_from_slice(string.as_bytes());
+        let mut code = vec![];
+        for instruction in &self.instructions {
+            if self.compact {
+                code.extend_from_slice(&instruction.to_compact_bytes());
+            } else {
+                code.extend_from_slice(&instruction.to_bfo_bytes());
+            }
         }
+        sections.push((SectionKind::Code, code));
 
-        for instruction in &self.instructions {
-            bytes.extend_from_slice(&instruction.to_bfo_bytes());
+        if !self.string_table.is_empty() {
+            let rodata: Vec<(Address, &[u8])> = self.string_table.iter()
+                .map(|(location, string)| (*location, string.as_bytes()))
+                .collect();
+            sections.push((SectionKind::ReadOnlyData, data_section(&rodata)));
         }
-        bytes
+        if !self.data.is_empty() {
+            let data: Vec<(Address, &[u8])> = self.data.iter()
+                .map(|(location, bytes)| (*location, bytes.as_slice()))
+                .collect();
+            sections.push((SectionKind::Data, data_section(&data)));
+        }
+        if !self.bss.is_empty() {
+            let mut bss = vec![];
+            bss.extend_from_slice(&(self.bss.len() as u32).to_le_bytes());
+            for (location, size) in &self.bss {
+                bss.extend_from_slice(&location.to_le_bytes());
+                bss.extend_from_slice(&size.to_le_bytes());
+            }
+            sections.push((SectionKind::Bss, bss));
+        }
+        if let Some(entry) = self.entry {
+            sections.push((SectionKind::Entry, entry.to_le_bytes().to_vec()));
+        }
+        if !self.symbols.is_empty() {
+            let mut symbols = vec![];
+            symbols.extend_from_slice(&(self.symbols.len() as u32).to_le_bytes());
+            for symbol in &self.symbols {
+                symbols.extend_from_slice(&symbol.value.to_le_bytes());
+                symbols.push(symbol.kind as u8);
+                symbols.push(symbol.binding as u8);
+                symbols.extend_from_slice(&(symbol.name.len() as u16).to_le_bytes());
+                symbols.extend_from_slice(symbol.name.as_bytes());
+            }
+            sections.push((SectionKind::Symbols, symbols));
+        }
+        if !self.debug.is_empty() {
+            sections.push((SectionKind::Debug, debug_section(&self.debug)));
+        }
+        if !self.relocations.is_empty() {
+            let mut relocations = vec![];
+            relocations.extend_from_slice(&(self.relocations.len() as u32).to_le_bytes());
+            for relocation in &self.relocations {
+                relocations.extend_from_slice(&relocation.index.to_le_bytes());
+                relocations.extend_from_slice(&(relocation.symbol.len() as u16).to_le_bytes());
+                relocations.extend_from_slice(relocation.symbol.as_bytes());
+            }
+            sections.push((SectionKind::Relocations, relocations));
+        }
+        sections.push((SectionKind::Checksum, vec![0; CHECKSUM_SIZE]));
+        if signed {
+            sections.push((SectionKind::Signature, vec![0; SIGNATURE_SIZE]));
+        }
+
+        bytes.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        let mut offset = bytes.len() + sections.len() * 12;
+        for (kind, section) in &sections {
+            bytes.extend_from_slice(&(*kind as u32).to_le_bytes());
+            bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+            bytes.extend_from_slice(&(section.len() as u32).to_le_bytes());
+            offset += section.len();
+        }
+        let mut checksum_offset = 0;
+        for (kind, section) in &sections {
+            if *kind == SectionKind::Checksum {
+                checksum_offset = bytes.len();
+            }
+            bytes.extend_from_slice(section);
+        }
+
+        let checksum = crc32fast::hash(&bytes[..checksum_offset]);
+        bytes[checksum_offset..checksum_offset + CHECKSUM_SIZE].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+// u32 count, then (u32 address, u32 length, bytes) per entry
+fn data_section(entries: &[(Address, &[u8])]) -> Vec<u8> {
+    let mut section = vec![];
+    section.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (location, bytes) in entries {
+        section.extend_from_slice(&location.to_le_bytes());
+        section.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        section.extend_from_slice(bytes);
     }
-}
\ No newline at end of file
+    section
+}
+
+// u32 name count and (u16 length, name) per name, file names and labels are stored once here,
+// then u32 count and (u32 instruction, u32 file, u32 line, u32 column, u32 label or u32::MAX) per location
+fn debug_section(debug: &DebugInfo) -> Vec<u8> {
+    let mut names: Vec<String> = vec![];
+    let mut name_index = |name: &str| -> u32 {
+        match names.iter().position(|existing| *existing == name) {
+            Some(index) => index as u32,
+            None => {
+                names.push(name.to_string());
+                names.len() as u32 - 1
+            }
+        }
+    };
+
+    let mut locations = vec![];
+    for (index, location) in &debug.locations {
+        let file = name_index(&location.file);
+        let label = location.label.as_deref().map_or(u32::MAX, &mut name_index);
+        for value in [*index, file, location.line, location.column, label] {
+            locations.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    let mut section = vec![];
+    section.extend_from_slice(&(names.len() as u32).to_le_bytes());
+    for name in &names {
+        section.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        section.extend_from_slice(name.as_bytes());
+    }
+    section.extend_from_slice(&(debug.locations.len() as u32).to_le_bytes());
+    section.extend_from_slice(&locations);
+    section
+}
@@ -0,0 +1,97 @@
+use crate::constants::types::Address;
+
+// the kind of each entry in a sectioned bfo's directory, readers skip kinds they don't know
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SectionKind {
+    Code = 1,
+    ReadOnlyData = 2,
+    Data = 3,
+    Bss = 4,
+    Entry = 5,
+    Symbols = 6,
+    Debug = 7,
+    Checksum = 8,
+    Signature = 9,
+    Relocations = 10,
+}
+
+// header flags, 0.3+ files use the 2 bytes after the version that used to be reserved
+pub const COMPACT_CODE: u16 = 1; // the code section uses the variable length encoding
+pub const OBJECT: u16 = 2; // a relocatable object, it has to go through bffld before it can run
+pub const KNOWN_FLAGS: u16 = COMPACT_CODE | OBJECT;
+
+// checksum and signature come last, they cover every byte of the file before them
+pub const CHECKSUM_SIZE: usize = 4; // crc32
+pub const SIGNATURE_SIZE: usize = 32 + 64; // ed25519 public key and signature
+
+impl SectionKind {
+    pub fn from_u32(kind: u32) -> Option<SectionKind> {
+        match kind {
+            1 => Some(SectionKind::Code),
+            2 => Some(SectionKind::ReadOnlyData),
+            3 => Some(SectionKind::Data),
+            4 => Some(SectionKind::Bss),
+            5 => Some(SectionKind::Entry),
+            6 => Some(SectionKind::Symbols),
+            7 => Some(SectionKind::Debug),
+            8 => Some(SectionKind::Checksum),
+            9 => Some(SectionKind::Signature),
+            10 => Some(SectionKind::Relocations),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SymbolKind {
+    Code = 0, // value is an instruction index
+    Data = 1, // value is a memory address
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SymbolBinding {
+    Local = 0,
+    Global = 1,
+    Extern = 2, // defined as a global in another object, the value is unused
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Symbol {
+    pub name: String,
+    pub value: Address,
+    pub kind: SymbolKind,
+    pub binding: SymbolBinding,
+}
+
+impl Symbol {
+    pub fn new(name: &str, value: Address, kind: SymbolKind, binding: SymbolBinding) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            kind,
+            binding,
+        }
+    }
+}
+
+// an operand in an object that bffld fills in with `symbol`'s final value, which operand depends on the instruction:
+// the target of Call, JumpImmediate and JumpNotZeroImmediate, the value of MoveImmediate and PushImmediate,
+// and the address of DirectLoad and DirectStore
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relocation {
+    pub index: Address, // instruction index, 1 is the first instruction
+    pub symbol: String,
+}
+
+impl Relocation {
+    pub fn new(index: Address, symbol: &str) -> Self {
+        Self {
+            index,
+            symbol: symbol.to_string(),
+        }
+    }
+}
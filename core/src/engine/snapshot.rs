@@ -0,0 +1,214 @@
+use std::fmt;
+use crate::constants::constants::{INSTRUCTION_SIZE, REGISTER_COUNT};
+use crate::constants::instructions::Instruction;
+use crate::constants::types::{Address, Bits, Byte};
+use crate::engine::memory::{MemoryRegion, Permissions};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"BFFS";
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnapshotError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    RegisterCount(usize),
+    InvalidInstruction(usize, u8), // index, opcode
+    InvalidRegister(usize, u8), // index, register
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a bff snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(f, "unsupported snapshot version {}, expected {}", version, SNAPSHOT_VERSION),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::RegisterCount(count) => write!(f, "snapshot has {} registers, expected {}", count, REGISTER_COUNT),
+            SnapshotError::InvalidInstruction(index, opcode) => write!(f, "invalid opcode {} at instruction {}", opcode, index),
+            SnapshotError::InvalidRegister(index, register) => write!(f, "invalid register {} at instruction {}", register, index),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+// everything needed to resume a vm where it left off, host state (syscalls, tracer, fuel costs) isn't included
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmSnapshot {
+    pub registers: [Bits; REGISTER_COUNT],
+    pub memory: Vec<Byte>,
+    pub regions: Vec<MemoryRegion>,
+    pub stack: Vec<Bits>,
+    pub call_stack: Vec<Address>,
+    pub max_call_depth: usize,
+    pub program_counter: usize,
+    pub instruction_list: Vec<Instruction>,
+    pub exit_code: Option<Bits>,
+}
+
+// all little endian:
+// magic "BFFS", u16 version, 2 reserved bytes,
+// u32 register count + registers, u64 program counter, u8 exited + u32 exit code, u64 max call depth,
+// then u64 length prefixed stack (u32), call stack (u32), memory (u8),
+// regions (u32 start, u64 length, u8 permissions: 1 read, 2 write) and instructions (8 bytes each)
+impl VmSnapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+
+        bytes.extend_from_slice(&(REGISTER_COUNT as u32).to_le_bytes());
+        for register in &self.registers {
+            bytes.extend_from_slice(&register.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.program_counter as u64).to_le_bytes());
+        bytes.push(self.exit_code.is_some() as u8);
+        bytes.extend_from_slice(&self.exit_code.unwrap_or(0).to_le_bytes());
+        bytes.extend_from_slice(&(self.max_call_depth as u64).to_le_bytes());
+
+        bytes.extend_from_slice(&(self.stack.len() as u64).to_le_bytes());
+        for value in &self.stack {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.call_stack.len() as u64).to_le_bytes());
+        for address in &self.call_stack {
+            bytes.extend_from_slice(&address.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.memory.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.memory);
+
+        bytes.extend_from_slice(&(self.regions.len() as u64).to_le_bytes());
+        for region in &self.regions {
+            bytes.extend_from_slice(&region.start.to_le_bytes());
+            bytes.extend_from_slice(&(region.length as u64).to_le_bytes());
+            bytes.push(region.permissions.read as u8 | (region.permissions.write as u8) << 1);
+        }
+
+        bytes.extend_from_slice(&(self.instruction_list.len() as u64).to_le_bytes());
+        for instruction in &self.instruction_list {
+            bytes.extend_from_slice(&instruction.to_bfo_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<VmSnapshot, SnapshotError> {
+        let mut reader = SnapshotReader { bytes, index: 0 };
+        if reader.take(4)? != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        reader.take(2)?;
+
+        let register_count = reader.u32()? as usize;
+        if register_count != REGISTER_COUNT {
+            return Err(SnapshotError::RegisterCount(register_count));
+        }
+        let mut registers = [0; REGISTER_COUNT];
+        for register in registers.iter_mut() {
+            *register = reader.u32()?;
+        }
+        let program_counter = reader.u64()? as usize;
+        let exited = reader.u8()? != 0;
+        let exit_code = reader.u32()?;
+        let max_call_depth = reader.u64()? as usize;
+
+        let stack = reader.u32_list()?;
+        let call_stack = reader.u32_list()?;
+        let memory_length = reader.length()?;
+        let memory = reader.take(memory_length)?.to_vec();
+
+        let region_count = reader.length()?;
+        let mut regions = vec![];
+        for _ in 0..region_count {
+            let start = reader.u32()?;
+            let length = reader.u64()? as usize;
+            let permissions = reader.u8()?;
+            regions.push(MemoryRegion::new(start, length, Permissions {
+                read: permissions & 1 != 0,
+                write: permissions & 2 != 0,
+            }));
+        }
+
+        let instruction_count = reader.length()?;
+        let mut instruction_list = vec![];
+        for index in 0..instruction_count {
+            let mut instruction = [0; INSTRUCTION_SIZE as usize];
+            instruction.copy_from_slice(reader.take(INSTRUCTION_SIZE as usize)?);
+            let instruction = Instruction::from_bfo_bytes(instruction)
+                .ok_or(SnapshotError::InvalidInstruction(index, instruction[0]))?;
+            if let Some(register) = instruction.invalid_register() {
+                return Err(SnapshotError::InvalidRegister(index, register));
+            }
+            instruction_list.push(instruction);
+        }
+
+        Ok(VmSnapshot {
+            registers,
+            memory,
+            regions,
+            stack,
+            call_stack,
+            max_call_depth,
+            program_counter,
+            instruction_list,
+            exit_code: if exited { Some(exit_code) } else { None },
+        })
+    }
+}
+
+struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+        let end = self.index.checked_add(length).ok_or(SnapshotError::Truncated)?;
+        let bytes = self.bytes.get(self.index..end).ok_or(SnapshotError::Truncated)?;
+        self.index = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // a length prefix, checked against what's left so a corrupt file can't make us allocate wildly
+    fn length(&mut self) -> Result<usize, SnapshotError> {
+        let length = self.u64()?;
+        if length > (self.bytes.len() - self.index) as u64 {
+            return Err(SnapshotError::Truncated);
+        }
+        Ok(length as usize)
+    }
+
+    fn u32_list(&mut self) -> Result<Vec<u32>, SnapshotError> {
+        let length = self.length()?;
+        let mut values = vec![];
+        for _ in 0..length {
+            values.push(self.u32()?);
+        }
+        Ok(values)
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use crate::constants::types::Bits;
+use crate::engine::trap::TrapReason;
+use crate::engine::virtual_machine::VirtualMachine;
+
+pub const SYSCALL_READ: Bits = 0;
+pub const SYSCALL_WRITE: Bits = 1;
+pub const SYSCALL_EXIT: Bits = 2;
+
+// a host function the guest reaches through `SystemCall`, the syscall number is already popped
+// and the handler pops its own arguments off the stack
+pub trait SyscallHandler {
+    fn call(&mut self, vm: &mut VirtualMachine) -> Result<(), TrapReason>;
+}
+
+impl<F: FnMut(&mut VirtualMachine) -> Result<(), TrapReason>> SyscallHandler for F {
+    fn call(&mut self, vm: &mut VirtualMachine) -> Result<(), TrapReason> {
+        self(vm)
+    }
+}
+
+#[derive(Default)]
+pub struct SyscallTable {
+    handlers: HashMap<Bits, Box<dyn SyscallHandler>>,
+    names: HashMap<String, Bits>,
+}
+
+impl fmt::Debug for SyscallTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut numbers: Vec<&Bits> = self.handlers.keys().collect();
+        numbers.sort();
+        f.debug_struct("SyscallTable")
+            .field("numbers", &numbers)
+            .field("names", &self.names)
+            .finish()
+    }
+}
+
+impl SyscallTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // read (0), write (1) and exit (2)
+    pub fn with_defaults() -> Self {
+        let mut table = Self::new();
+        table.register_named("read", SYSCALL_READ, read);
+        table.register_named("write", SYSCALL_WRITE, write);
+        table.register_named("exit", SYSCALL_EXIT, exit);
+        table
+    }
+
+    // replaces any handler already registered under `number`
+    pub fn register<H: SyscallHandler + 'static>(&mut self, number: Bits, handler: H) {
+        self.handlers.insert(number, Box::new(handler));
+    }
+
+    pub fn register_named<H: SyscallHandler + 'static>(&mut self, name: &str, number: Bits, handler: H) {
+        self.register(number, handler);
+        self.names.insert(name.to_string(), number);
+    }
+
+    pub fn unregister(&mut self, number: Bits) {
+        self.handlers.remove(&number);
+        self.names.retain(|_, registered| *registered != number);
+    }
+
+    pub fn number_of(&self, name: &str) -> Option<Bits> {
+        self.names.get(name).copied()
+    }
+
+    pub fn contains(&self, number: Bits) -> bool {
+        self.handlers.contains_key(&number)
+    }
+
+    // the handler is taken out of the table while it runs so it can borrow the vm mutably
+    pub(crate) fn take(&mut self, number: Bits) -> Option<Box<dyn SyscallHandler>> {
+        self.handlers.remove(&number)
+    }
+
+    pub(crate) fn restore(&mut self, number: Bits, handler: Box<dyn SyscallHandler>) {
+        self.handlers.entry(number).or_insert(handler);
+    }
+}
+
+// reads a u32 from stdin and pushes it
+fn read(vm: &mut VirtualMachine) -> Result<(), TrapReason> {
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).map_err(|e| TrapReason::SyscallFailed(e.to_string()))?;
+    let input = input.trim();
+    let input = input.parse::<u32>().map_err(|e| TrapReason::SyscallFailed(e.to_string()))?;
+    vm.push_stack(input)
+}
+
+// pops a file descriptor (0 stdout, 1 stderr), an address and a length, and writes that memory out
+fn write(vm: &mut VirtualMachine) -> Result<(), TrapReason> {
+    let file_descriptor = vm.pop_stack()?;
+    let address = vm.pop_stack()?;
+    let length = vm.pop_stack()?;
+
+    let buffer = vm.read_memory(address, length as usize)?;
+    let result = match file_descriptor {
+        0 => {
+            io::stdout().write_all(buffer)
+        }
+        1 => {
+            io::stderr().write_all(buffer)
+        }
+        _ => {
+            return Err(TrapReason::InvalidFileDescriptor(file_descriptor));
+        }
+    };
+    result.map_err(|e| TrapReason::SyscallFailed(e.to_string()))
+}
+
+// pops the exit status
+fn exit(vm: &mut VirtualMachine) -> Result<(), TrapReason> {
+    vm.exit_code = Some(vm.pop_stack()?);
+    Ok(())
+}
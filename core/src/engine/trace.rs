@@ -0,0 +1,148 @@
+use std::fmt;
+use std::io;
+use std::io::Write;
+use crate::constants::constants::REGISTER_COUNT;
+use crate::constants::instructions::Instruction;
+use crate::constants::types::{Address, Bits, Byte};
+use crate::engine::trap::TrapReason;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StackEvent {
+    Push(Bits),
+    Pop(Bits),
+}
+
+// everything one executed instruction changed
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceRecord {
+    pub program_counter: usize,
+    pub instruction: Instruction,
+    pub registers: Vec<(usize, Bits, Bits)>, // register, before, after
+    pub memory_writes: Vec<(Address, Vec<Byte>)>,
+    pub stack: Vec<StackEvent>,
+    pub trap: Option<TrapReason>,
+}
+
+impl TraceRecord {
+    pub fn new(program_counter: usize, instruction: Instruction) -> Self {
+        Self {
+            program_counter,
+            instruction,
+            registers: vec![],
+            memory_writes: vec![],
+            stack: vec![],
+            trap: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let registers: Vec<String> = self.registers.iter()
+            .map(|(register, before, after)| format!("{{\"register\":{},\"before\":{},\"after\":{}}}", register, before, after))
+            .collect();
+        let memory_writes: Vec<String> = self.memory_writes.iter()
+            .map(|(address, bytes)| {
+                let bytes: Vec<String> = bytes.iter().map(|byte| byte.to_string()).collect();
+                format!("{{\"address\":{},\"bytes\":[{}]}}", address, bytes.join(","))
+            })
+            .collect();
+        let stack: Vec<String> = self.stack.iter()
+            .map(|event| match event {
+                StackEvent::Push(value) => format!("{{\"push\":{}}}", value),
+                StackEvent::Pop(value) => format!("{{\"pop\":{}}}", value),
+            })
+            .collect();
+
+        let mut json = format!(
+            "{{\"pc\":{},\"instruction\":\"{}\",\"registers\":[{}],\"memory\":[{}],\"stack\":[{}]",
+            self.program_counter,
+            escape_json(&format!("{:?}", self.instruction)),
+            registers.join(","),
+            memory_writes.join(","),
+            stack.join(","),
+        );
+        if let Some(trap) = &self.trap {
+            json.push_str(&format!(",\"trap\":\"{}\"", escape_json(&trap.to_string())));
+        }
+        json.push('}');
+        json
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// writes one json line per executed instruction, see `TraceRecord::to_json`
+pub struct Tracer {
+    output: Box<dyn Write>,
+    record: Option<TraceRecord>,
+    registers_before: [Bits; REGISTER_COUNT],
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer").field("record", &self.record).finish()
+    }
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            record: None,
+            registers_before: [0; REGISTER_COUNT],
+        }
+    }
+
+    pub fn begin(&mut self, program_counter: usize, instruction: Instruction, registers: &[Bits; REGISTER_COUNT]) {
+        self.record = Some(TraceRecord::new(program_counter, instruction));
+        self.registers_before = *registers;
+    }
+
+    pub fn push(&mut self, value: Bits) {
+        if let Some(record) = &mut self.record {
+            record.stack.push(StackEvent::Push(value));
+        }
+    }
+
+    pub fn pop(&mut self, value: Bits) {
+        if let Some(record) = &mut self.record {
+            record.stack.push(StackEvent::Pop(value));
+        }
+    }
+
+    pub fn write_memory(&mut self, address: Address, bytes: &[Byte]) {
+        if let Some(record) = &mut self.record {
+            record.memory_writes.push((address, bytes.to_vec()));
+        }
+    }
+
+    // tracing never fails the guest, so write errors are dropped here
+    pub fn finish(&mut self, registers: &[Bits; REGISTER_COUNT], trap: Option<&TrapReason>) {
+        if let Some(mut record) = self.record.take() {
+            for (register, (before, after)) in self.registers_before.iter().zip(registers.iter()).enumerate() {
+                if before != after {
+                    record.registers.push((register, *before, *after));
+                }
+            }
+            record.trap = trap.cloned();
+            writeln!(self.output, "{}", record.to_json()).ok();
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+use crate::constants::instructions::Instruction;
+use crate::constants::types::{Address, Bits, Byte, Register};
+use crate::engine::memory::Access;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrapReason {
+    DivisionByZero,
+    StackUnderflow,
+    StackOverflow,
+    CallStackUnderflow,
+    CallStackOverflow,
+    MemoryOutOfBounds(Address),
+    ProtectionFault(Address, Access),
+    InvalidByteSize(Byte),
+    InvalidSyscall(Bits),
+    InvalidFileDescriptor(Bits),
+    SyscallFailed(String),
+    InvalidRegister(Register),
+    InvalidProgramCounter(usize),
+}
+
+// a guest fault, raised instead of panicking so the host can report it and carry on
+#[derive(Clone, Debug, PartialEq)]
+pub struct VmTrap {
+    pub program_counter: usize,
+    pub instruction: Instruction,
+    pub reason: TrapReason,
+}
+
+impl VmTrap {
+    pub fn new(program_counter: usize, instruction: Instruction, reason: TrapReason) -> Self {
+        Self {
+            program_counter,
+            instruction,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for TrapReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapReason::DivisionByZero => write!(f, "division by zero"),
+            TrapReason::StackUnderflow => write!(f, "stack underflow"),
+            TrapReason::StackOverflow => write!(f, "stack overflow"),
+            TrapReason::CallStackUnderflow => write!(f, "call stack underflow"),
+            TrapReason::CallStackOverflow => write!(f, "call stack overflow"),
+            TrapReason::MemoryOutOfBounds(address) => write!(f, "memory access out of bounds at address {}", address),
+            TrapReason::ProtectionFault(address, access) => write!(f, "protection fault, {} not allowed at address {}", access, address),
+            TrapReason::InvalidByteSize(size) => write!(f, "invalid byte size {}", size),
+            TrapReason::InvalidSyscall(number) => write!(f, "invalid syscall number {}", number),
+            TrapReason::InvalidFileDescriptor(descriptor) => write!(f, "invalid file descriptor {}", descriptor),
+            TrapReason::SyscallFailed(message) => write!(f, "syscall failed: {}", message),
+            TrapReason::InvalidRegister(register) => write!(f, "invalid register {}", register),
+            TrapReason::InvalidProgramCounter(program_counter) => write!(f, "program counter {} is outside the program", program_counter),
+        }
+    }
+}
+
+impl fmt::Display for VmTrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "trap at pc={} ({:?}): {}", self.program_counter, self.instruction, self.reason)
+    }
+}
+
+impl std::error::Error for VmTrap {}
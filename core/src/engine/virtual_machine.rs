@@ -1,9 +1,21 @@
-use std::io;
-use std::io::Write;
 use std::ops::Neg;
-use crate::constants::constants::{BASE_MEMORY_SIZE, BASE_STACK_SIZE, REGISTER_COUNT, STACK_POINTER};
+use crate::constants::constants::{REGISTER_COUNT, STACK_POINTER};
 use crate::constants::instructions::Instruction;
-use crate::constants::types::{Address, Bits, Byte};
+use crate::constants::types::{Address, Bits, Byte, Register};
+use crate::engine::config::VmConfig;
+use crate::engine::fuel::FuelCosts;
+use crate::engine::memory::{Access, MemoryRegion, Permissions};
+use crate::engine::snapshot::VmSnapshot;
+use crate::engine::syscalls::SyscallTable;
+use crate::engine::trace::Tracer;
+use crate::engine::trap::{TrapReason, VmTrap};
+
+#[derive(Debug, PartialEq)]
+pub enum ExecutionState {
+    Running, // ran out of fuel, call execute_for again to continue
+    Halted(Bits),
+    Trapped(VmTrap),
+}
 
 #[derive(Debug)]
 pub struct VirtualMachine {
@@ -11,457 +23,608 @@ pub struct VirtualMachine {
     pub registers: [Bits; REGISTER_COUNT],
     pub stack: Vec<Bits>,
     pub memory: Vec<Byte>,
+    pub regions: Vec<MemoryRegion>, // guest accesses outside every region fault
     pub call_stack: Vec<Address>,
+    pub max_call_depth: usize,
     pub program_counter: usize,
     pub instruction_list: Vec<Instruction>,
+    pub exit_code: Option<Bits>, // set once the guest halts or exits
+    pub fuel_costs: FuelCosts,
+    pub tracer: Option<Tracer>,
+    pub syscalls: SyscallTable,
+}
+
+impl Default for VirtualMachine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VirtualMachine {
     pub fn new() -> VirtualMachine {
+        Self::with_config(&VmConfig::default())
+    }
+    pub fn with_config(config: &VmConfig) -> VirtualMachine {
         VirtualMachine {
-            registers: [0; REGISTER_COUNT],
-            stack: vec![0; BASE_STACK_SIZE],
-            memory: vec![0; BASE_MEMORY_SIZE],
+            registers: config.registers,
+            stack: vec![0; config.stack_size],
+            memory: vec![0; config.memory_size],
+            regions: vec![MemoryRegion::new(0, config.memory_size, Permissions::READ_WRITE)],
             call_stack: vec![],
+            max_call_depth: config.max_call_depth,
             program_counter: 0,
             instruction_list: vec![Instruction::Nop],
+            exit_code: None,
+            fuel_costs: FuelCosts::default(),
+            tracer: None,
+            syscalls: SyscallTable::with_defaults(),
         }
     }
-    pub fn pop_stack(&mut self) -> Bits {
-        self.registers[STACK_POINTER as usize] -= 1;
-        let value = self.stack[self.registers[STACK_POINTER as usize] as usize];
-        value
+    pub fn pop_stack(&mut self) -> Result<Bits, TrapReason> {
+        let stack_pointer = self.registers[STACK_POINTER as usize];
+        if stack_pointer == 0 {
+            return Err(TrapReason::StackUnderflow);
+        }
+        let value = *self.stack.get(stack_pointer as usize - 1).ok_or(TrapReason::StackOverflow)?;
+        self.registers[STACK_POINTER as usize] = stack_pointer - 1;
+        if let Some(tracer) = &mut self.tracer {
+            tracer.pop(value);
+        }
+        Ok(value)
     }
-    pub fn push_stack(&mut self, value: Bits) {
-        self.stack[self.registers[STACK_POINTER as usize] as usize] = value;
-        self.registers[STACK_POINTER as usize] += 1;
+    pub fn push_stack(&mut self, value: Bits) -> Result<(), TrapReason> {
+        let stack_pointer = self.registers[STACK_POINTER as usize];
+        let slot = self.stack.get_mut(stack_pointer as usize).ok_or(TrapReason::StackOverflow)?;
+        *slot = value;
+        self.registers[STACK_POINTER as usize] = stack_pointer + 1;
+        if let Some(tracer) = &mut self.tracer {
+            tracer.push(value);
+        }
+        Ok(())
     }
-    fn store(&mut self, address: Address, value: Bits, size: u8) {
-        match size {
-            0 => {
-                let value = value.to_le_bytes();
-                self.memory[address as usize] = value[0];
-                self.memory[address as usize + 1] = value[1];
-                self.memory[address as usize + 2] = value[2];
-                self.memory[address as usize + 3] = value[3];
-            }
-            2 => {
-                let value = (value as u16).to_le_bytes();
-                self.memory[address as usize] = value[0];
-                self.memory[address as usize + 1] = value[1];
-            }
-            3 => {
-                self.memory[address as usize] = value as u8;
-            }
-            _ => {
-                panic!("byte size is invalid! for store instruction");
+    fn memory_range(&self, address: Address, length: usize) -> Result<std::ops::Range<usize>, TrapReason> {
+        let start = address as usize;
+        if start + length > self.memory.len() {
+            return Err(TrapReason::MemoryOutOfBounds(address));
+        }
+        Ok(start..start + length)
+    }
+    fn checked_range(&self, address: Address, length: usize, access: Access) -> Result<std::ops::Range<usize>, TrapReason> {
+        let range = self.memory_range(address, length)?;
+        for byte in range.clone() {
+            if !self.permissions_at(byte as Address).allows(access) {
+                return Err(TrapReason::ProtectionFault(byte as Address, access));
             }
         }
+        Ok(range)
+    }
+    // memory that isn't in any region can't be accessed at all
+    pub fn permissions_at(&self, address: Address) -> Permissions {
+        self.regions.iter()
+            .rev()
+            .find(|region| region.contains(address as usize))
+            .map(|region| region.permissions)
+            .unwrap_or(Permissions::NONE)
     }
-    fn load(&mut self, address: Address, size: u8) -> Bits {
+    pub fn map_region(&mut self, start: Address, length: usize, permissions: Permissions) -> Result<(), TrapReason> {
+        self.memory_range(start, length)?;
+        self.regions.push(MemoryRegion::new(start, length, permissions));
+        Ok(())
+    }
+    fn store(&mut self, address: Address, value: Bits, size: u8) -> Result<(), TrapReason> {
+        let length = match size {
+            0 => 4,
+            2 => 2,
+            3 => 1,
+            _ => return Err(TrapReason::InvalidByteSize(size)),
+        };
+        self.write_memory(address, &value.to_le_bytes()[..length])
+    }
+    fn load(&mut self, address: Address, size: u8) -> Result<Bits, TrapReason> {
         match size {
             0 => {
+                let range = self.checked_range(address, 4, Access::Read)?;
                 let mut value = [0; 4];
-                value[0] = self.memory[address as usize];
-                value[1] = self.memory[address as usize + 1];
-                value[2] = self.memory[address as usize + 2];
-                value[3] = self.memory[address as usize + 3];
-                Bits::from_le_bytes(value)
+                value.copy_from_slice(&self.memory[range]);
+                Ok(Bits::from_le_bytes(value))
             }
             2 => {
+                let range = self.checked_range(address, 2, Access::Read)?;
                 let mut value = [0; 2];
-                value[0] = self.memory[address as usize];
-                value[1] = self.memory[address as usize + 1];
-                u16::from_le_bytes(value) as Bits
+                value.copy_from_slice(&self.memory[range]);
+                Ok(u16::from_le_bytes(value) as Bits)
             }
             3 => {
-                self.memory[address as usize] as Bits
+                let range = self.checked_range(address, 1, Access::Read)?;
+                Ok(self.memory[range.start] as Bits)
             }
             _ => {
-                panic!("byte size is invalid! for load instruction");
+                Err(TrapReason::InvalidByteSize(size))
             }
         }
     }
-    pub fn store_string(&mut self, address: Address, value: &str) {
-        for (index, byte) in value.bytes().into_iter().enumerate() {
-            self.memory[address as usize + index] = byte;
+    pub fn read_memory(&self, address: Address, length: usize) -> Result<&[Byte], TrapReason> {
+        let range = self.checked_range(address, length, Access::Read)?;
+        Ok(&self.memory[range])
+    }
+    pub fn write_memory(&mut self, address: Address, bytes: &[Byte]) -> Result<(), TrapReason> {
+        let range = self.checked_range(address, bytes.len(), Access::Write)?;
+        self.memory[range].copy_from_slice(bytes);
+        if let Some(tracer) = &mut self.tracer {
+            tracer.write_memory(address, bytes);
+        }
+        Ok(())
+    }
+    // host side, ignores region permissions
+    pub fn store_bytes(&mut self, address: Address, bytes: &[Byte]) -> Result<(), TrapReason> {
+        let range = self.memory_range(address, bytes.len())?;
+        self.memory[range].copy_from_slice(bytes);
+        Ok(())
+    }
+    pub fn store_string(&mut self, address: Address, value: &str) -> Result<(), TrapReason> {
+        self.store_bytes(address, value.as_bytes())
+    }
+    // register operands come straight from the program, so a bad one traps instead of panicking
+    fn register(&self, register: Register) -> Result<Bits, TrapReason> {
+        self.registers.get(register as usize).copied().ok_or(TrapReason::InvalidRegister(register))
+    }
+    fn register_mut(&mut self, register: Register) -> Result<&mut Bits, TrapReason> {
+        self.registers.get_mut(register as usize).ok_or(TrapReason::InvalidRegister(register))
+    }
+    // the pc before a jump or call target, stepping moves it onto the target. 1 is the first instruction,
+    // one past the last ends the program like running off the end does, anything else traps
+    fn jump_target(&self, target: Bits) -> Result<usize, TrapReason> {
+        if target == 0 || target as usize > self.instruction_list.len() {
+            return Err(TrapReason::InvalidProgramCounter(target as usize));
         }
+        Ok(target as usize - 1)
     }
-    pub fn execute_single_instruction(&mut self){
-        let instruction = &self.instruction_list[self.program_counter];
-        match *instruction {
+    pub fn execute_single_instruction(&mut self) -> Result<(), VmTrap> {
+        let instruction = self.instruction_list.get(self.program_counter).copied()
+            .ok_or_else(|| VmTrap::new(self.program_counter, Instruction::Nop, TrapReason::InvalidProgramCounter(self.program_counter)))?;
+        if let Some(tracer) = &mut self.tracer {
+            tracer.begin(self.program_counter, instruction, &self.registers);
+        }
+
+        let result = self.execute(instruction);
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.finish(&self.registers, result.as_ref().err());
+        }
+        result.map_err(|reason| VmTrap::new(self.program_counter, instruction, reason))
+    }
+    fn execute(&mut self, instruction: Instruction) -> Result<(), TrapReason> {
+        match instruction {
             Instruction::Nop => {
                 // do nothing
             }
             Instruction::Add(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] + self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_add(self.register(rhs)?);
             }
             Instruction::AddImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] + rhs;
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_add(rhs);
             }
             Instruction::Sub(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] - self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_sub(self.register(rhs)?);
             }
             Instruction::SubImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] - rhs;
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_sub(rhs);
             }
             Instruction::Mul(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] * self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_mul(self.register(rhs)?);
             }
             Instruction::MulImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] * rhs;
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_mul(rhs);
             }
             Instruction::DivMod(div_dst, mod_dst, lhs, rhs) => {
-                self.registers[div_dst as usize] = self.registers[lhs as usize] / self.registers[rhs as usize];
-                self.registers[mod_dst as usize] = self.registers[lhs as usize] % self.registers[rhs as usize];
+                *self.register_mut(div_dst)? = divide(self.register(lhs)?, self.register(rhs)?)?;
+                *self.register_mut(mod_dst)? = remainder(self.register(lhs)?, self.register(rhs)?)?;
             }
             Instruction::DivModImmediate(div_dst, mod_dst, lhs, rhs) => {
-                self.registers[div_dst as usize] = self.registers[lhs as usize] / rhs;
-                self.registers[mod_dst as usize] = self.registers[lhs as usize] % rhs;
+                *self.register_mut(div_dst)? = divide(self.register(lhs)?, rhs)?;
+                *self.register_mut(mod_dst)? = remainder(self.register(lhs)?, rhs)?;
             }
             Instruction::Div(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] / self.registers[rhs as usize];
+                *self.register_mut(dst)? = divide(self.register(lhs)?, self.register(rhs)?)?;
             }
             Instruction::DivImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] / rhs;
+                *self.register_mut(dst)? = divide(self.register(lhs)?, rhs)?;
             }
             Instruction::Mod(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] % self.registers[rhs as usize];
+                *self.register_mut(dst)? = remainder(self.register(lhs)?, self.register(rhs)?)?;
             }
             Instruction::ModImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] % rhs;
+                *self.register_mut(dst)? = remainder(self.register(lhs)?, rhs)?;
             }
 
             Instruction::GreaterThan(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] > self.registers[rhs as usize] { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? > self.register(rhs)? { 1 } else { 0 };
             }
             Instruction::GreaterThanImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] > rhs { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? > rhs { 1 } else { 0 };
             }
             Instruction::LessThan(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] < self.registers[rhs as usize] { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? < self.register(rhs)? { 1 } else { 0 };
             }
             Instruction::LessThanImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] < rhs { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? < rhs { 1 } else { 0 };
             }
             Instruction::GreaterThanOrEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] >= self.registers[rhs as usize] { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? >= self.register(rhs)? { 1 } else { 0 };
             }
             Instruction::GreaterThanOrEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] >= rhs { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? >= rhs { 1 } else { 0 };
             }
             Instruction::LessThanOrEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] <= self.registers[rhs as usize] { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? <= self.register(rhs)? { 1 } else { 0 };
             }
             Instruction::LessThanOrEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] <= rhs { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? <= rhs { 1 } else { 0 };
             }
             Instruction::Equal(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] == self.registers[rhs as usize] { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? == self.register(rhs)? { 1 } else { 0 };
             }
             Instruction::EqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] == rhs { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? == rhs { 1 } else { 0 };
             }
             Instruction::NotEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] != self.registers[rhs as usize] { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? != self.register(rhs)? { 1 } else { 0 };
             }
             Instruction::NotEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] != rhs { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? != rhs { 1 } else { 0 };
             }
 
             Instruction::FloatAdd(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) + f32::from_bits(self.registers[rhs as usize])).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) + f32::from_bits(self.register(rhs)?)).to_bits();
             }
             Instruction::FloatAddImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) + f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) + f32::from_bits(rhs)).to_bits();
             }
             Instruction::FloatSub(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) - f32::from_bits(self.registers[rhs as usize])).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) - f32::from_bits(self.register(rhs)?)).to_bits();
             }
             Instruction::FloatSubImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) - f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) - f32::from_bits(rhs)).to_bits();
             }
             Instruction::FloatMul(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) * f32::from_bits(self.registers[rhs as usize])).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) * f32::from_bits(self.register(rhs)?)).to_bits();
             }
             Instruction::FloatMulImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) * f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) * f32::from_bits(rhs)).to_bits();
             }
             Instruction::FloatDiv(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) / f32::from_bits(self.registers[rhs as usize])).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) / f32::from_bits(self.register(rhs)?)).to_bits();
             }
             Instruction::FloatDivImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) / f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) / f32::from_bits(rhs)).to_bits();
             }
             Instruction::FloatMod(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) % f32::from_bits(self.registers[rhs as usize])).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) % f32::from_bits(self.register(rhs)?)).to_bits();
             }
             Instruction::FloatModImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (f32::from_bits(self.registers[lhs as usize]) % f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst)? = (f32::from_bits(self.register(lhs)?) % f32::from_bits(rhs)).to_bits();
             }
             Instruction::FloatDivMod(dst_div, dst_mod, lhs, rhs) => {
-                self.registers[dst_div as usize] = (f32::from_bits(self.registers[lhs as usize]) / f32::from_bits(self.registers[rhs as usize])).to_bits();
-                self.registers[dst_mod as usize] = (f32::from_bits(self.registers[lhs as usize]) % f32::from_bits(self.registers[rhs as usize])).to_bits();
+                *self.register_mut(dst_div)? = (f32::from_bits(self.register(lhs)?) / f32::from_bits(self.register(rhs)?)).to_bits();
+                *self.register_mut(dst_mod)? = (f32::from_bits(self.register(lhs)?) % f32::from_bits(self.register(rhs)?)).to_bits();
             }
             Instruction::FloatDivModImmediate(dst_div, dst_mod, lhs, rhs) => {
-                self.registers[dst_div as usize] = (f32::from_bits(self.registers[lhs as usize]) / f32::from_bits(rhs)).to_bits();
-                self.registers[dst_mod as usize] = (f32::from_bits(self.registers[lhs as usize]) % f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst_div)? = (f32::from_bits(self.register(lhs)?) / f32::from_bits(rhs)).to_bits();
+                *self.register_mut(dst_mod)? = (f32::from_bits(self.register(lhs)?) % f32::from_bits(rhs)).to_bits();
             }
 
             Instruction::FloatGreaterThan(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) > f32::from_bits(self.registers[rhs as usize]) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) > f32::from_bits(self.register(rhs)?) { 1 } else { 0 };
             }
             Instruction::FloatGreaterThanImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) > f32::from_bits(rhs) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) > f32::from_bits(rhs) { 1 } else { 0 };
             }
             Instruction::FloatLessThan(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) < f32::from_bits(self.registers[rhs as usize]) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) < f32::from_bits(self.register(rhs)?) { 1 } else { 0 };
             }
             Instruction::FloatLessThanImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) < f32::from_bits(rhs) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) < f32::from_bits(rhs) { 1 } else { 0 };
             }
             Instruction::FloatGreaterThanOrEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) >= f32::from_bits(self.registers[rhs as usize]) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) >= f32::from_bits(self.register(rhs)?) { 1 } else { 0 };
             }
             Instruction::FloatGreaterThanOrEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) >= f32::from_bits(rhs) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) >= f32::from_bits(rhs) { 1 } else { 0 };
             }
             Instruction::FloatLessThanOrEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) <= f32::from_bits(self.registers[rhs as usize]) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) <= f32::from_bits(self.register(rhs)?) { 1 } else { 0 };
             }
             Instruction::FloatLessThanOrEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if f32::from_bits(self.registers[lhs as usize]) <= f32::from_bits(rhs) { 1 } else { 0 };
+                *self.register_mut(dst)? = if f32::from_bits(self.register(lhs)?) <= f32::from_bits(rhs) { 1 } else { 0 };
             }
             Instruction::FloatNegate(dst, src) => {
-                self.registers[dst as usize] = f32::from_bits(self.registers[src as usize]).neg().to_bits();
+                *self.register_mut(dst)? = f32::from_bits(self.register(src)?).neg().to_bits();
             }
             Instruction::FloatNegateImmediate(dst, src) => {
-                self.registers[dst as usize] = f32::from_bits(src).neg().to_bits();
+                *self.register_mut(dst)? = f32::from_bits(src).neg().to_bits();
             }
 
             Instruction::SignedAdd(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 + self.registers[rhs as usize] as i32) as u32;
+                *self.register_mut(dst)? = (self.register(lhs)? as i32).wrapping_add(self.register(rhs)? as i32) as u32;
             }
             Instruction::SignedAddImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 + rhs as i32) as u32;
+                *self.register_mut(dst)? = (self.register(lhs)? as i32).wrapping_add(rhs as i32) as u32;
             }
             Instruction::SignedSub(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 - self.registers[rhs as usize] as i32) as u32;
+                *self.register_mut(dst)? = (self.register(lhs)? as i32).wrapping_sub(self.register(rhs)? as i32) as u32;
             }
             Instruction::SignedSubImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 - rhs as i32) as u32;
+                *self.register_mut(dst)? = (self.register(lhs)? as i32).wrapping_sub(rhs as i32) as u32;
             }
             Instruction::SignedMul(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 * self.registers[rhs as usize] as i32) as u32;
+                *self.register_mut(dst)? = (self.register(lhs)? as i32).wrapping_mul(self.register(rhs)? as i32) as u32;
             }
             Instruction::SignedMulImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 * rhs as i32) as u32;
+                *self.register_mut(dst)? = (self.register(lhs)? as i32).wrapping_mul(rhs as i32) as u32;
             }
             Instruction::SignedDiv(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 / self.registers[rhs as usize] as i32) as u32;
+                *self.register_mut(dst)? = signed_divide(self.register(lhs)? as i32, self.register(rhs)? as i32)? as u32;
             }
             Instruction::SignedDivImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 / rhs as i32) as u32;
+                *self.register_mut(dst)? = signed_divide(self.register(lhs)? as i32, rhs as i32)? as u32;
             }
             Instruction::SignedMod(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 % self.registers[rhs as usize] as i32) as u32;
+                *self.register_mut(dst)? = signed_remainder(self.register(lhs)? as i32, self.register(rhs)? as i32)? as u32;
             }
             Instruction::SignedModImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = (self.registers[lhs as usize] as i32 % rhs as i32) as u32;
+                *self.register_mut(dst)? = signed_remainder(self.register(lhs)? as i32, rhs as i32)? as u32;
             }
             Instruction::SignedDivMod(dst_div, dst_mod, lhs, rhs) => {
-                let lhs = self.registers[lhs as usize] as i32;
-                let rhs = self.registers[rhs as usize] as i32;
-                self.registers[dst_div as usize] = (lhs / rhs) as u32;
-                self.registers[dst_mod as usize] = (lhs % rhs) as u32;
+                let lhs = self.register(lhs)? as i32;
+                let rhs = self.register(rhs)? as i32;
+                *self.register_mut(dst_div)? = signed_divide(lhs, rhs)? as u32;
+                *self.register_mut(dst_mod)? = signed_remainder(lhs, rhs)? as u32;
             }
             Instruction::SignedDivModImmediate(dst_div, dst_mod, lhs, rhs) => {
-                let lhs = self.registers[lhs as usize] as i32;
+                let lhs = self.register(lhs)? as i32;
                 let rhs = rhs as i32;
-                self.registers[dst_div as usize] = (lhs / rhs) as u32;
-                self.registers[dst_mod as usize] = (lhs % rhs) as u32;
+                *self.register_mut(dst_div)? = signed_divide(lhs, rhs)? as u32;
+                *self.register_mut(dst_mod)? = signed_remainder(lhs, rhs)? as u32;
             }
 
             Instruction::SignedGreaterThan(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] as i32 > self.registers[rhs as usize] as i32 { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? as i32 > self.register(rhs)? as i32 { 1 } else { 0 };
             }
             Instruction::SignedGreaterThanImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] as i32 > rhs as i32 { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? as i32 > rhs as i32 { 1 } else { 0 };
             }
             Instruction::SignedGreaterThanOrEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] as i32 >= self.registers[rhs as usize] as i32 { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? as i32 >= self.register(rhs)? as i32 { 1 } else { 0 };
             }
             Instruction::SignedGreaterThanOrEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] as i32 >= rhs as i32 { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? as i32 >= rhs as i32 { 1 } else { 0 };
             }
             Instruction::SignedLessThan(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if (self.registers[lhs as usize] as i32) < (self.registers[rhs as usize] as i32) { 1 } else { 0 };
+                *self.register_mut(dst)? = if (self.register(lhs)? as i32) < (self.register(rhs)? as i32) { 1 } else { 0 };
             }
             Instruction::SignedLessThanImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if (self.registers[lhs as usize] as i32) < (rhs as i32) { 1 } else { 0 };
+                *self.register_mut(dst)? = if (self.register(lhs)? as i32) < (rhs as i32) { 1 } else { 0 };
             }
             Instruction::SignedLessThanOrEqual(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] as i32 <= self.registers[rhs as usize] as i32 { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? as i32 <= self.register(rhs)? as i32 { 1 } else { 0 };
             }
             Instruction::SignedLessThanOrEqualImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = if self.registers[lhs as usize] as i32 <= rhs as i32 { 1 } else { 0 };
+                *self.register_mut(dst)? = if self.register(lhs)? as i32 <= rhs as i32 { 1 } else { 0 };
             }
             Instruction::SignedNegate(dst, src) => {
-                self.registers[dst as usize] = -(self.registers[src as usize] as i32) as u32;
+                *self.register_mut(dst)? = (self.register(src)? as i32).wrapping_neg() as u32;
             }
             Instruction::SignedNegateImmediate(dst, src) => {
-                self.registers[dst as usize] = -(src as i32) as u32;
+                *self.register_mut(dst)? = (src as i32).wrapping_neg() as u32;
             }
 
 
             Instruction::And(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] & self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)? & self.register(rhs)?;
             }
             Instruction::AndImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] & rhs;
+                *self.register_mut(dst)? = self.register(lhs)? & rhs;
             }
             Instruction::Or(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] | self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)? | self.register(rhs)?;
             }
             Instruction::OrImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] | rhs;
+                *self.register_mut(dst)? = self.register(lhs)? | rhs;
             }
             Instruction::Xor(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] ^ self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)? ^ self.register(rhs)?;
             }
             Instruction::XorImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] ^ rhs;
+                *self.register_mut(dst)? = self.register(lhs)? ^ rhs;
             }
             Instruction::Not(dst, src) => {
-                self.registers[dst as usize] = !self.registers[src as usize];
+                *self.register_mut(dst)? = !self.register(src)?;
             }
             Instruction::NotImmediate(dst, src) => {
-                self.registers[dst as usize] = !src;
+                *self.register_mut(dst)? = !src;
             }
 
             Instruction::ShiftLeft(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] << self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_shl(self.register(rhs)?);
             }
             Instruction::ShiftLeftImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] << rhs;
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_shl(rhs);
             }
             Instruction::ShiftRight(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] >> self.registers[rhs as usize];
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_shr(self.register(rhs)?);
             }
             Instruction::ShiftRightImmediate(dst, lhs, rhs) => {
-                self.registers[dst as usize] = self.registers[lhs as usize] >> rhs;
+                *self.register_mut(dst)? = self.register(lhs)?.wrapping_shr(rhs);
             }
 
             Instruction::Jump(register) => {
-                self.program_counter = (self.registers[register as usize] - 1) as usize;
+                self.program_counter = self.jump_target(self.register(register)?)?;
             }
             Instruction::JumpImmediate(address) => {
-                self.program_counter = (address - 1) as usize;
+                self.program_counter = self.jump_target(address)?;
             }
             Instruction::JumpNotZero(register, address_register) => {
-                if self.registers[register as usize] != 0 {
-                    self.program_counter = (self.registers[address_register as usize] - 1) as usize;
+                if self.register(register)? != 0 {
+                    self.program_counter = self.jump_target(self.register(address_register)?)?;
                 }
             }
             Instruction::JumpNotZeroImmediate(src, address) => {
-                if self.registers[src as usize] != 0 {
-                    self.program_counter = (address - 1) as usize;
+                if self.register(src)? != 0 {
+                    self.program_counter = self.jump_target(address)?;
                 }
             }
 
             Instruction::Move(dst, src) => {
-                self.registers[dst as usize] = self.registers[src as usize];
+                *self.register_mut(dst)? = self.register(src)?;
             }
             Instruction::MoveImmediate(dst, src) => {
-                self.registers[dst as usize] = src;
+                *self.register_mut(dst)? = src;
             }
 
             Instruction::Push(src) => {
-                self.push_stack(self.registers[src as usize])
+                self.push_stack(self.register(src)?)?;
             }
             Instruction::PushImmediate(src) => {
-                self.push_stack(src)
+                self.push_stack(src)?;
             }
             Instruction::Pop(dst) => {
-                self.registers[dst as usize] = self.pop_stack();
+                *self.register_mut(dst)? = self.pop_stack()?;
             }
 
             Instruction::Store(address, src, byte_num) => {
-                let address = self.registers[address as usize];
-                self.store(address, self.registers[src as usize], byte_num)
+                let address = self.register(address)?;
+                self.store(address, self.register(src)?, byte_num)?;
             }
             Instruction::DirectStore(address, src, byte_num) => {
-                self.store(address, self.registers[src as usize], byte_num)
+                self.store(address, self.register(src)?, byte_num)?;
             }
             Instruction::Load(dst, address, byte_num) => {
-                self.registers[dst as usize] = self.load(self.registers[address as usize], byte_num);
+                *self.register_mut(dst)? = self.load(self.register(address)?, byte_num)?;
             }
             Instruction::DirectLoad(dst, address, byte_num) => {
-                self.registers[dst as usize] = self.load(address, byte_num);
+                *self.register_mut(dst)? = self.load(address, byte_num)?;
             }
 
             Instruction::Call(address) => {
+                if self.call_stack.len() >= self.max_call_depth {
+                    return Err(TrapReason::CallStackOverflow);
+                }
+                let target = self.jump_target(address)?;
                 self.call_stack.push(self.program_counter as Address);
-                self.program_counter = (address - 1) as usize;
+                self.program_counter = target;
             }
             Instruction::Return => {
                 self.program_counter = match self.call_stack.pop() {
-                    None => { panic!("Call stack underflow!"); }
+                    None => { return Err(TrapReason::CallStackUnderflow); }
                     Some(address) => { address as usize }
                 };
             }
             Instruction::SystemCall => {
-                let syscall_num = self.pop_stack();
-                match syscall_num {
-                    0 => {
-                        let mut input = String::new();
-                        io::stdin().read_line(&mut input).expect("Failed to read line");
-                        let input = input.trim();
-                        let input = input.parse::<u32>().expect("Failed to parse input");
-                        self.push_stack(input);
-                    }
-                    1 => {
-                        let file_descriptor = self.pop_stack();
-                        let address = self.pop_stack();
-                        let length = self.pop_stack();
-
-                        let mut buffer = vec![0u8; length as usize];
-                        for i in 0..length {
-                            buffer[i as usize] = self.memory[(address + i) as usize];
-                        }
-                        let string = String::from_utf8(buffer).expect("Failed to parse string");
+                let syscall_num = self.pop_stack()?;
+                let mut handler = self.syscalls.take(syscall_num).ok_or(TrapReason::InvalidSyscall(syscall_num))?;
+                let result = handler.call(self);
+                self.syscalls.restore(syscall_num, handler);
+                result?;
+            }
 
-                        match file_descriptor {
-                            0 => {
-                                let mut stdout = io::stdout();
-                                stdout.write_all(string.as_bytes()).expect("Failed to write to stdout");
-                            }
-                            1 => {
-                                let mut stderr = io::stderr();
-                                stderr.write_all(string.as_bytes()).expect("Failed to write to stderr");
-                            }
-                            _ => {
-                                panic!("Invalid file descriptor!");
-                            }
-                        }
-                    }
-                    _ => {
-                        panic!("Invalid syscall number!");
-                    }
-                }
+            Instruction::Halt => {
+                self.exit_code = Some(0);
+            }
+            Instruction::Exit(src) => {
+                self.exit_code = Some(self.register(src)?);
+            }
+            Instruction::ExitImmediate(code) => {
+                self.exit_code = Some(code);
             }
         }
+        Ok(())
     }
 
-    pub fn execute_instruction_list(&mut self) {
-        while self.program_counter < self.instruction_list.len() {
-            self.execute_single_instruction();
-            self.program_counter += 1;
+    pub fn is_finished(&self) -> bool {
+        self.exit_code.is_some() || self.program_counter >= self.instruction_list.len()
+    }
+
+    pub fn step(&mut self) -> Result<(), VmTrap> {
+        self.execute_single_instruction()?;
+        self.program_counter += 1;
+        Ok(())
+    }
+
+    // runs until the guest halts or runs off the end of the program, returning its exit status
+    pub fn execute_instruction_list(&mut self) -> Result<Bits, VmTrap> {
+        while !self.is_finished() {
+            self.step()?;
         }
+        Ok(self.exit_code.unwrap_or(0))
+    }
+
+    // like execute_instruction_list, but stops before the instruction that would exceed the fuel budget
+    pub fn execute_for(&mut self, fuel: u64) -> ExecutionState {
+        let mut remaining = fuel;
+        while !self.is_finished() {
+            let cost = self.instruction_list.get(self.program_counter).map_or(0, |instruction| self.fuel_costs.cost(instruction.class()));
+            if cost > remaining {
+                return ExecutionState::Running;
+            }
+            remaining -= cost;
+
+            if let Err(trap) = self.step() {
+                return ExecutionState::Trapped(trap);
+            }
+        }
+        ExecutionState::Halted(self.exit_code.unwrap_or(0))
     }
 
     pub fn load_program(&mut self, instruction_list: Vec<Instruction>) {
         self.instruction_list = vec![Instruction::Nop];
         self.instruction_list.extend(instruction_list);
     }
-}
\ No newline at end of file
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            registers: self.registers,
+            memory: self.memory.clone(),
+            regions: self.regions.clone(),
+            stack: self.stack.clone(),
+            call_stack: self.call_stack.clone(),
+            max_call_depth: self.max_call_depth,
+            program_counter: self.program_counter,
+            instruction_list: self.instruction_list.clone(),
+            exit_code: self.exit_code,
+        }
+    }
+    // keeps the syscalls, tracer and fuel costs of this vm
+    pub fn restore(&mut self, snapshot: VmSnapshot) {
+        self.registers = snapshot.registers;
+        self.memory = snapshot.memory;
+        self.regions = snapshot.regions;
+        self.stack = snapshot.stack;
+        self.call_stack = snapshot.call_stack;
+        self.max_call_depth = snapshot.max_call_depth;
+        self.program_counter = snapshot.program_counter;
+        self.instruction_list = snapshot.instruction_list;
+        self.exit_code = snapshot.exit_code;
+    }
+}
+
+fn divide(lhs: Bits, rhs: Bits) -> Result<Bits, TrapReason> {
+    lhs.checked_div(rhs).ok_or(TrapReason::DivisionByZero)
+}
+
+fn remainder(lhs: Bits, rhs: Bits) -> Result<Bits, TrapReason> {
+    lhs.checked_rem(rhs).ok_or(TrapReason::DivisionByZero)
+}
+
+// i32::MIN / -1 wraps instead of faulting, the same as the other signed ops
+fn signed_divide(lhs: i32, rhs: i32) -> Result<i32, TrapReason> {
+    if rhs == 0 {
+        return Err(TrapReason::DivisionByZero);
+    }
+    Ok(lhs.wrapping_div(rhs))
+}
+
+fn signed_remainder(lhs: i32, rhs: i32) -> Result<i32, TrapReason> {
+    if rhs == 0 {
+        return Err(TrapReason::DivisionByZero);
+    }
+    Ok(lhs.wrapping_rem(rhs))
+}
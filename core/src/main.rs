@@ -1,6 +1,5 @@
 // for active testing
 
-use bffcore;
 use bffcore::constants::instructions::Instruction;
 use bffcore::constants::types::{Address, Register};
 use bffcore::engine::virtual_machine::VirtualMachine;
@@ -10,7 +9,7 @@ fn string_to_instructions(address: u32, register: u8, input: &str) -> Vec<Instru
     let mut index = 0;
     while input.len() > index {
         if (input.len() - index) >= 4 {
-            let mut byte_array: [u8; 4] = input[index..index + 4].as_bytes().try_into().unwrap();
+            let byte_array: [u8; 4] = input.as_bytes()[index..index + 4].try_into().unwrap();
             let value = u32::from_le_bytes(byte_array);
 
             instructions.extend(
@@ -21,7 +20,7 @@ fn string_to_instructions(address: u32, register: u8, input: &str) -> Vec<Instru
             );
             index += 4;
         } else if (input.len() - index) >= 2 {
-            let mut byte_array: [u8; 2] = input[index..index + 2].as_bytes().try_into().unwrap();
+            let byte_array: [u8; 2] = input.as_bytes()[index..index + 2].try_into().unwrap();
             let value = u16::from_le_bytes(byte_array);
 
             instructions.extend(
@@ -32,7 +31,7 @@ fn string_to_instructions(address: u32, register: u8, input: &str) -> Vec<Instru
             );
             index += 2;
         } else {
-            let value = input[index..index + 1].as_bytes()[0] as u32;
+            let value = input.as_bytes()[index] as u32;
 
             instructions.extend(
                 vec![
@@ -64,7 +63,7 @@ fn print_string_as_instructions(address: Address, register: Register, string: St
 
 fn main(){
     let mut vm = VirtualMachine::new(); // hello world
-    let mut instructions =
+    let instructions =
         print_string_as_instructions(
             0,
             1,
@@ -80,5 +79,7 @@ fn main(){
     vm.load_program(instructions);
 
     println!(" -- VM OutPut --");
-    vm.execute_instruction_list();
+    if let Err(trap) = vm.execute_instruction_list() {
+        eprintln!("{}", trap);
+    }
 }
\ No newline at end of file
@@ -1,14 +1,18 @@
-use bffcore::constants::constants::INSTRUCTION_COUNT;
+use bffcore::constants::constants::{INSTRUCTION_COUNT, INSTRUCTION_SIZE, VERSION};
 use bffcore::constants::instructions::Instruction;
+use bffcore::engine::bfo_reader::{BFOReader, BfoError};
+use bffcore::engine::debug_info::SourceLocation;
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::{Symbol, SymbolBinding, SymbolKind};
 
 #[test]
 fn instruction_conversion_integrity_check(){
-    assert_eq!(INSTRUCTION_COUNT, 95);
+    assert_eq!(INSTRUCTION_COUNT, 100);
 
 
     for i in 0..INSTRUCTION_COUNT as u8 {
         let raw = [i, 0, 0, 0, 0, 0, 0, 0];
-        let instruction = Instruction::from_bfo_bytes(raw);
+        let instruction = Instruction::from_bfo_bytes(raw).unwrap();
         let bytes  = instruction.to_bfo_bytes();
 
         assert_eq!(raw[0], bytes[0]);
@@ -22,4 +26,220 @@ fn instruction_conversion_integrity_check(){
 
         println!("Passed: {:?}", instruction)
     }
-}
\ No newline at end of file
+
+    assert_eq!(Instruction::from_bfo_bytes([INSTRUCTION_COUNT as u8, 0, 0, 0, 0, 0, 0, 0]), None);
+}
+fn version_header(version: (u16, u16, u16)) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&version.0.to_le_bytes());
+    bytes.extend_from_slice(&version.1.to_le_bytes());
+    bytes.extend_from_slice(&version.2.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+}
+
+// a 0.2 header, before sections
+fn header(string_count: u64) -> Vec<u8> {
+    let mut bytes = version_header((0, 2, 0));
+    bytes.extend_from_slice(&string_count.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn reads_program_back() {
+    let program = BFFProgram::new(VERSION, vec![Instruction::PushImmediate(7), Instruction::Halt], vec![(4, "hi".to_string())]);
+    let read = BFOReader::read_program(program.to_bfo_bytes()).unwrap();
+    assert_eq!(read.instructions, program.instructions);
+    assert_eq!(read.string_table, program.string_table);
+}
+
+#[test]
+fn corrupt_files_are_errors() {
+    assert_eq!(BFOReader::read_program(vec![0, 0, 1]).err(), Some(BfoError::TruncatedHeader(3)));
+
+    assert_eq!(
+        BFOReader::read_program(version_header((0, 9, 0))).err(),
+        Some(BfoError::VersionMismatch { expected: VERSION, found: (0, 9, 0) })
+    );
+
+    let mut bytes = header(2);
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(b"hi");
+    bytes.extend_from_slice(&5u32.to_le_bytes());
+    assert_eq!(BFOReader::read_program(bytes).err(), Some(BfoError::TruncatedStringEntry { entry: 1, offset: 26 }));
+
+    let mut bytes = header(0);
+    bytes.extend_from_slice(&Instruction::Halt.to_bfo_bytes());
+    bytes.extend_from_slice(&[1, 2, 3]);
+    assert_eq!(BFOReader::read_program(bytes).err(), Some(BfoError::TrailingPartialInstruction { offset: 24, length: 3 }));
+
+    let mut bytes = header(0);
+    bytes.extend_from_slice(&Instruction::Halt.to_bfo_bytes());
+    bytes.extend_from_slice(&[200, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(BFOReader::read_program(bytes).err(), Some(BfoError::UnknownOpcode { offset: 24, opcode: 200 }));
+
+    let mut bytes = header(0);
+    bytes.extend_from_slice(&Instruction::Halt.to_bfo_bytes());
+    bytes.extend_from_slice(&Instruction::DirectStore(16, 17, 4).to_bfo_bytes());
+    assert_eq!(BFOReader::read_program(bytes).err(), Some(BfoError::InvalidRegister { offset: 24, register: 17 }));
+
+    assert!(matches!(BFOReader::read_file_from_path("/nonexistent.bfo".to_string()), Err(BfoError::Io(_))));
+}
+
+#[test]
+fn sectioned_program_round_trips() {
+    let mut program = BFFProgram::new(VERSION, vec![Instruction::PushImmediate(7), Instruction::Halt], vec![(4, "hi".to_string())]);
+    program.data = vec![(16, vec![1, 2, 3])];
+    program.bss = vec![(32, 64)];
+    program.entry = Some(2);
+    program.symbols = vec![
+        Symbol::new("main", 1, SymbolKind::Code, SymbolBinding::Global),
+        Symbol::new("buffer", 32, SymbolKind::Data, SymbolBinding::Local),
+    ];
+    program.debug.insert(2, SourceLocation::new("main.bffasm", 3, 1, Some("main")));
+    program.debug.insert(1, SourceLocation::new("main.bffasm", 2, 5, None));
+    assert_eq!(BFOReader::read_program(program.to_bfo_bytes()).unwrap(), program);
+    assert_eq!(program.debug.lookup(2).unwrap().to_string(), "main.bffasm:3 in main");
+    assert_eq!(program.debug.lookup(1).unwrap().column, 5);
+    assert_eq!(program.debug.lookup(3), None);
+
+    let legacy = BFFProgram::new((0, 2, 0), vec![Instruction::Halt], vec![(4, "hi".to_string())]);
+    assert_eq!(BFOReader::read_program(legacy.to_bfo_bytes()).unwrap(), legacy);
+}
+
+#[test]
+fn unknown_sections_are_skipped() {
+    let mut bytes = version_header(VERSION);
+    bytes.extend_from_slice(&3u32.to_le_bytes());
+    for (kind, offset, length) in [(99u32, 48u32, 3u32), (1, 51, 8), (8, 59, 4)] {
+        bytes.extend_from_slice(&kind.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[7, 7, 7]);
+    bytes.extend_from_slice(&Instruction::Halt.to_bfo_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+    assert_eq!(BFOReader::read_program(bytes.clone()).unwrap().instructions, vec![Instruction::Halt]);
+
+    bytes.truncate(bytes.len() - 5);
+    assert_eq!(BFOReader::read_program(bytes).err(), Some(BfoError::TruncatedSection { kind: 1, offset: 51 }));
+}
+
+#[test]
+fn every_version_has_a_decoder() {
+    // 0.1 has no string count, the instructions follow the version
+    let mut bytes = version_header((0, 1, 0));
+    bytes.extend_from_slice(&Instruction::PushImmediate(3).to_bfo_bytes());
+    bytes.extend_from_slice(&Instruction::Halt.to_bfo_bytes());
+    let mut program = BFOReader::read_program(bytes.clone()).unwrap();
+    assert_eq!(program, BFFProgram::new((0, 1, 0), vec![Instruction::PushImmediate(3), Instruction::Halt], vec![]));
+    assert_eq!(program.to_bfo_bytes(), bytes);
+
+    program.upgrade();
+    assert_eq!(program.version, VERSION);
+    assert_eq!(BFOReader::read_program(program.to_bfo_bytes()).unwrap(), program);
+
+    assert_eq!(
+        BFOReader::read_program(version_header((0, 0, 1))).err(),
+        Some(BfoError::UnsupportedVersion((0, 0, 1)))
+    );
+    assert_eq!(
+        BFOReader::read_program(version_header((1, 0, 0))).err(),
+        Some(BfoError::VersionMismatch { expected: VERSION, found: (1, 0, 0) })
+    );
+}
+
+#[test]
+fn checksum_catches_changed_bytes() {
+    let program = BFFProgram::new(VERSION, vec![Instruction::PushImmediate(7), Instruction::Halt], vec![(4, "hi".to_string())]);
+    let bytes = program.to_bfo_bytes();
+    assert_eq!(BFOReader::read_program(bytes.clone()).unwrap(), program);
+
+    // the push's immediate, the checksum is the last 4 bytes
+    let mut changed = bytes.clone();
+    let immediate = changed.iter().position(|byte| *byte == 7).unwrap();
+    changed[immediate] = 8;
+    assert!(matches!(BFOReader::read_program(changed), Err(BfoError::ChecksumMismatch { .. })));
+
+    let mut truncated = bytes.clone();
+    truncated.truncate(bytes.len() - 2);
+    assert!(matches!(BFOReader::read_program(truncated), Err(BfoError::TruncatedSection { kind: 8, .. })));
+
+    // dropping the checksum's directory entry doesn't get around the check
+    let mut stripped = bytes.clone();
+    let count_offset = 8;
+    let count = u32::from_le_bytes(stripped[count_offset..count_offset + 4].try_into().unwrap());
+    let checksum_entry = count_offset + 4 + (count as usize - 1) * 12;
+    assert_eq!(&stripped[checksum_entry..checksum_entry + 4], &8u32.to_le_bytes());
+    stripped[count_offset..count_offset + 4].copy_from_slice(&(count - 1).to_le_bytes());
+    assert_eq!(BFOReader::read_program(stripped).err(), Some(BfoError::MissingChecksum));
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn signed_programs_are_verified() {
+    use ed25519_dalek::SigningKey;
+
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let other_key = SigningKey::from_bytes(&[2; 32]);
+    let program = BFFProgram::new(VERSION, vec![Instruction::PushImmediate(7), Instruction::Halt], vec![]);
+    let signed = program.to_signed_bfo_bytes(&key).unwrap();
+
+    assert_eq!(BFOReader::read_signed_program(signed.clone(), &key.verifying_key()).unwrap(), program);
+    assert_eq!(BFOReader::read_program(signed.clone()).unwrap(), program);
+    assert_eq!(BFOReader::read_signed_program(signed.clone(), &other_key.verifying_key()).err(), Some(BfoError::BadSignature));
+    assert_eq!(BFOReader::read_signed_program(program.to_bfo_bytes(), &key.verifying_key()).err(), Some(BfoError::MissingSignature));
+
+    // a changed signature, the checksum doesn't cover it
+    let mut changed = signed.clone();
+    *changed.last_mut().unwrap() ^= 1;
+    assert_eq!(BFOReader::read_signed_program(changed, &key.verifying_key()).err(), Some(BfoError::BadSignature));
+
+    let legacy = BFFProgram::new((0, 2, 0), vec![Instruction::Halt], vec![]);
+    assert_eq!(legacy.to_signed_bfo_bytes(&key), None);
+}
+
+#[test]
+fn compact_encoding_round_trips_every_opcode() {
+    for opcode in 0..INSTRUCTION_COUNT as u8 {
+        let mut bytes = [0xab; INSTRUCTION_SIZE as usize];
+        bytes[0] = opcode;
+        let instruction = Instruction::from_bfo_bytes(bytes).unwrap();
+
+        // nothing past the compact size may carry an operand
+        let size = Instruction::compact_size(opcode).unwrap();
+        assert!(instruction.to_bfo_bytes()[size..].iter().all(|byte| *byte == 0), "{:?}", instruction);
+        let compact = instruction.to_compact_bytes();
+        assert_eq!(compact.len(), size);
+        assert_eq!(Instruction::from_compact_bytes(&compact), Some((instruction, size)));
+        assert_eq!(Instruction::from_compact_bytes(&compact[..size - 1]), None);
+    }
+    assert_eq!(Instruction::compact_size(INSTRUCTION_COUNT as u8), None);
+}
+
+#[test]
+fn compact_programs_round_trip() {
+    let mut program = BFFProgram::new(VERSION, vec![
+        Instruction::MoveImmediate(1, 10),
+        Instruction::SubImmediate(1, 1, 1),
+        Instruction::JumpNotZeroImmediate(1, 2),
+        Instruction::Push(1),
+        Instruction::Return,
+        Instruction::SystemCall,
+        Instruction::Halt,
+    ], vec![]);
+    let fixed = program.to_bfo_bytes();
+    program.compact = true;
+    let compact = program.to_bfo_bytes();
+    assert_eq!(compact.len(), fixed.len() - 7 * INSTRUCTION_SIZE as usize + (6 + 7 + 6 + 2 + 1 + 1 + 1));
+    assert_eq!(BFOReader::read_program(compact.clone()).unwrap(), program);
+
+    let mut flagged = compact.clone();
+    flagged[6] = 0x81;
+    assert_eq!(BFOReader::read_program(flagged).err(), Some(BfoError::UnknownFlags(0x80)));
+
+    program.instructions[3] = Instruction::Push(200);
+    let bad_register = program.to_bfo_bytes();
+    assert!(matches!(BFOReader::read_program(bad_register), Err(BfoError::InvalidRegister { register: 200, .. })));
+}
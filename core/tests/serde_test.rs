@@ -0,0 +1,59 @@
+#![cfg(feature = "serde")]
+
+use bffcore::constants::constants::VERSION;
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::config::VmConfig;
+use bffcore::engine::debug_info::SourceLocation;
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::{Relocation, Symbol, SymbolBinding, SymbolKind};
+use bffcore::engine::snapshot::VmSnapshot;
+
+#[test]
+fn programs_round_trip_through_json() {
+    let mut program = BFFProgram::new(VERSION, vec![
+        Instruction::MoveImmediate(1, 5),
+        Instruction::FloatAddImmediate(1, 1, 1.5f32.to_bits()),
+        Instruction::Call(0),
+        Instruction::Halt,
+    ], vec![(0, "hi".to_string())]);
+    program.data = vec![(16, vec![1, 2, 3])];
+    program.bss = vec![(32, 8)];
+    program.entry = Some(1);
+    program.symbols = vec![Symbol::new("start", 1, SymbolKind::Code, SymbolBinding::Global)];
+    program.debug.insert(1, SourceLocation::new("main.bffasm", 3, 1, Some("start")));
+    program.compact = true;
+    program.object = true;
+    program.relocations = vec![Relocation::new(3, "print")];
+
+    let json = serde_json::to_string(&program).unwrap();
+    assert!(json.contains("{\"MoveImmediate\":[1,5]}"), "{}", json);
+    let read: BFFProgram = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, program);
+    assert_eq!(read.to_bfo_bytes(), program.to_bfo_bytes());
+}
+
+#[test]
+fn json_programs_only_need_a_version_and_instructions() {
+    let json = r#"{"version": [0, 3, 0], "instructions": [{"MoveImmediate": [1, 7]}, {"Exit": 1}]}"#;
+    let mut program: BFFProgram = serde_json::from_str(json).unwrap();
+    assert_eq!(program, BFFProgram::new(VERSION, vec![Instruction::MoveImmediate(1, 7), Instruction::Exit(1)], vec![]));
+    assert_eq!(program.execute().unwrap(), 7);
+}
+
+#[test]
+fn json_programs_with_bad_registers_are_rejected() {
+    let json = r#"{"version": [0, 3, 0], "instructions": [{"MoveImmediate": [1, 7]}, {"Exit": 17}]}"#;
+    assert_eq!(BFFProgram::from_json(json).err(), Some("invalid register 17 in instruction 1".to_string()));
+}
+
+#[test]
+fn snapshots_round_trip_through_json() {
+    let mut vm = VmConfig::new().memory_size(64).build();
+    vm.load_program(vec![Instruction::MoveImmediate(1, 9), Instruction::PushImmediate(4), Instruction::Halt]);
+    vm.step().unwrap();
+    vm.step().unwrap();
+
+    let snapshot = vm.snapshot();
+    let read: VmSnapshot = serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+    assert_eq!(read, snapshot);
+}
@@ -0,0 +1,56 @@
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::syscalls::{SyscallHandler, SyscallTable, SYSCALL_WRITE};
+use bffcore::engine::trap::TrapReason;
+use bffcore::engine::virtual_machine::VirtualMachine;
+
+struct Counter {
+    calls: u32,
+}
+
+impl SyscallHandler for Counter {
+    fn call(&mut self, vm: &mut VirtualMachine) -> Result<(), TrapReason> {
+        self.calls += 1;
+        vm.push_stack(self.calls)
+    }
+}
+
+#[test]
+fn host_syscalls_get_stack_registers_and_memory() {
+    let mut vm = VirtualMachine::new();
+    vm.syscalls.register(10, Counter { calls: 0 });
+    vm.syscalls.register_named("store_sum", 11, |vm: &mut VirtualMachine| {
+        let address = vm.pop_stack()?;
+        let sum = vm.registers[1] + vm.registers[2];
+        vm.write_memory(address, &sum.to_le_bytes())
+    });
+    assert_eq!(vm.syscalls.number_of("store_sum"), Some(11));
+
+    vm.load_program(vec![
+        Instruction::PushImmediate(10),
+        Instruction::SystemCall,
+        Instruction::PushImmediate(10),
+        Instruction::SystemCall,
+        Instruction::Pop(2),
+        Instruction::Pop(1),
+        Instruction::PushImmediate(16), // address
+        Instruction::PushImmediate(11),
+        Instruction::SystemCall,
+        Instruction::DirectLoad(3, 16, 0),
+    ]);
+    vm.execute_instruction_list().unwrap();
+    assert_eq!(vm.registers[1], 1);
+    assert_eq!(vm.registers[2], 2);
+    assert_eq!(vm.registers[3], 3);
+}
+
+#[test]
+fn unregistered_syscall_traps() {
+    let mut vm = VirtualMachine::new();
+    vm.syscalls = SyscallTable::new();
+    assert!(!vm.syscalls.contains(SYSCALL_WRITE));
+    vm.load_program(vec![
+        Instruction::PushImmediate(SYSCALL_WRITE),
+        Instruction::SystemCall,
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::InvalidSyscall(SYSCALL_WRITE));
+}
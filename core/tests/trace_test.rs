@@ -0,0 +1,54 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::trace::Tracer;
+use bffcore::engine::virtual_machine::VirtualMachine;
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_records_registers_memory_and_stack() {
+    let buffer = SharedBuffer::default();
+    let mut vm = VirtualMachine::new();
+    vm.tracer = Some(Tracer::new(Box::new(buffer.clone())));
+    vm.load_program(vec![
+        Instruction::MoveImmediate(1, 72),
+        Instruction::DirectStore(8, 1, 3),
+        Instruction::Push(1),
+        Instruction::Pop(2),
+    ]);
+    vm.execute_instruction_list().unwrap();
+
+    let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], r#"{"pc":0,"instruction":"Nop","registers":[],"memory":[],"stack":[]}"#);
+    assert_eq!(lines[1], r#"{"pc":1,"instruction":"MoveImmediate(1, 72)","registers":[{"register":1,"before":0,"after":72}],"memory":[],"stack":[]}"#);
+    assert_eq!(lines[2], r#"{"pc":2,"instruction":"DirectStore(8, 1, 3)","registers":[],"memory":[{"address":8,"bytes":[72]}],"stack":[]}"#);
+    assert_eq!(lines[3], r#"{"pc":3,"instruction":"Push(1)","registers":[{"register":0,"before":0,"after":1}],"memory":[],"stack":[{"push":72}]}"#);
+    assert_eq!(lines[4], r#"{"pc":4,"instruction":"Pop(2)","registers":[{"register":0,"before":1,"after":0},{"register":2,"before":0,"after":72}],"memory":[],"stack":[{"pop":72}]}"#);
+}
+
+#[test]
+fn trace_records_trap() {
+    let buffer = SharedBuffer::default();
+    let mut vm = VirtualMachine::new();
+    vm.tracer = Some(Tracer::new(Box::new(buffer.clone())));
+    vm.load_program(vec![Instruction::Return]);
+    assert!(vm.execute_instruction_list().is_err());
+
+    let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    assert!(output.lines().last().unwrap().ends_with(r#""trap":"call stack underflow"}"#));
+}
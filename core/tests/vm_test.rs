@@ -1,6 +1,12 @@
-use bffcore;
 use bffcore::constants::instructions::Instruction;
-use bffcore::engine::virtual_machine::VirtualMachine;
+use bffcore::constants::constants::{BASE_MEMORY_SIZE, VERSION};
+use bffcore::engine::config::VmConfig;
+use bffcore::engine::fuel::FuelCosts;
+use bffcore::engine::memory::{Access, Permissions};
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::snapshot::{SnapshotError, VmSnapshot};
+use bffcore::engine::trap::TrapReason;
+use bffcore::engine::virtual_machine::{ExecutionState, VirtualMachine};
 
 #[test]
 fn unsigned_add() {
@@ -14,7 +20,7 @@ fn unsigned_add() {
         Instruction::MoveImmediate(2, rhs),
         Instruction::Add(1, 1, 2),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -30,7 +36,7 @@ fn unsigned_sub() {
         Instruction::MoveImmediate(2, rhs),
         Instruction::Sub(1, 1, 2),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -46,7 +52,7 @@ fn unsigned_mul() {
         Instruction::MoveImmediate(2, rhs),
         Instruction::Mul(1, 1, 2),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -62,7 +68,7 @@ fn unsigned_div() {
         Instruction::MoveImmediate(2, rhs),
         Instruction::Div(1, 1, 2),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -78,7 +84,7 @@ fn unsigned_mod() {
         Instruction::MoveImmediate(2, rhs),
         Instruction::Mod(1, 1, 2),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -95,7 +101,7 @@ fn unsigned_div_mod() {
         Instruction::MoveImmediate(2, rhs),
         Instruction::DivMod(3, 4, 1, 2),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[3], expected_div);
     assert_eq!(vm.registers[4], expected_mod);
 }
@@ -111,7 +117,7 @@ fn unsigned_add_imm() {
         Instruction::MoveImmediate(1, lhs),
         Instruction::AddImmediate(1, 1,rhs),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -126,7 +132,7 @@ fn unsigned_sub_imm() {
         Instruction::MoveImmediate(1, lhs),
         Instruction::SubImmediate(1, 1, rhs),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -141,7 +147,7 @@ fn unsigned_mul_imm() {
         Instruction::MoveImmediate(1, lhs),
         Instruction::MulImmediate(1, 1, rhs),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -156,7 +162,7 @@ fn unsigned_div_imm() {
         Instruction::MoveImmediate(1, lhs),
         Instruction::DivImmediate(1, 1, rhs),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -171,7 +177,7 @@ fn unsigned_mod_imm() {
         Instruction::MoveImmediate(1, lhs),
         Instruction::ModImmediate(1, 1, rhs),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[1], expected);
 }
 
@@ -187,7 +193,7 @@ fn unsigned_div_mod_imm() {
         Instruction::MoveImmediate(1, lhs),
         Instruction::DivModImmediate(3, 4, 1, rhs),
     ]);
-    vm.execute_instruction_list();
+    vm.execute_instruction_list().unwrap();
     assert_eq!(vm.registers[3], expected_div);
     assert_eq!(vm.registers[4], expected_mod);
 }
@@ -200,8 +206,276 @@ fn print_hello_world(){
         Instruction::Store(0, 1, 3),
         Instruction::PushImmediate(1), // length
         Instruction::PushImmediate(0), // address
+        Instruction::PushImmediate(0), // file descriptor
         Instruction::PushImmediate(1), // syscall number
         Instruction::SystemCall
     ]);
-    vm.execute_instruction_list();
-}
\ No newline at end of file
+    vm.execute_instruction_list().unwrap();
+}
+#[test]
+fn division_by_zero_traps() {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::MoveImmediate(1, 34),
+        Instruction::DivImmediate(1, 1, 0),
+    ]);
+    let trap = vm.execute_instruction_list().unwrap_err();
+    assert_eq!(trap.program_counter, 2);
+    assert_eq!(trap.instruction, Instruction::DivImmediate(1, 1, 0));
+    assert_eq!(trap.reason, TrapReason::DivisionByZero);
+}
+
+#[test]
+fn stack_and_call_stack_underflow_trap() {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::Pop(1)]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::StackUnderflow);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::Return]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::CallStackUnderflow);
+}
+
+#[test]
+fn bad_registers_and_program_counter_trap() {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::MoveImmediate(200, 1)]);
+    let trap = vm.execute_instruction_list().unwrap_err();
+    assert_eq!((trap.program_counter, trap.reason), (1, TrapReason::InvalidRegister(200)));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::Add(1, 2, 17)]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::InvalidRegister(17));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::Halt]);
+    vm.program_counter = 5;
+    assert_eq!(vm.execute_single_instruction().unwrap_err().reason, TrapReason::InvalidProgramCounter(5));
+}
+
+#[test]
+fn jumps_outside_the_program_trap() {
+    let run = |program: Vec<Instruction>| {
+        let mut vm = VirtualMachine::new();
+        vm.load_program(program);
+        vm.execute_instruction_list().map_err(|trap| (trap.program_counter, trap.reason))
+    };
+    let exit = Instruction::Exit(1);
+    assert_eq!(run(vec![Instruction::MoveImmediate(1, 7), Instruction::JumpImmediate(0), exit]), Err((2, TrapReason::InvalidProgramCounter(0))));
+    assert_eq!(run(vec![Instruction::MoveImmediate(2, 0), Instruction::Jump(2), exit]), Err((2, TrapReason::InvalidProgramCounter(0))));
+    assert_eq!(run(vec![Instruction::MoveImmediate(1, 1), Instruction::JumpNotZeroImmediate(1, 0), exit]), Err((2, TrapReason::InvalidProgramCounter(0))));
+    assert_eq!(run(vec![Instruction::Call(0), exit]), Err((1, TrapReason::InvalidProgramCounter(0))));
+    assert_eq!(run(vec![Instruction::JumpImmediate(10), exit]), Err((1, TrapReason::InvalidProgramCounter(10))));
+    assert_eq!(run(vec![Instruction::MoveImmediate(2, 6), Instruction::MoveImmediate(1, 1), Instruction::JumpNotZero(1, 2), exit]), Err((3, TrapReason::InvalidProgramCounter(6))));
+    assert_eq!(run(vec![Instruction::Call(u32::MAX), exit]), Err((1, TrapReason::InvalidProgramCounter(u32::MAX as usize))));
+
+    // one past the last instruction ends the program like running off the end
+    assert_eq!(run(vec![Instruction::MoveImmediate(1, 7), Instruction::JumpImmediate(4), exit]), Ok(0));
+}
+
+#[test]
+fn out_of_bounds_memory_traps() {
+    let address = BASE_MEMORY_SIZE as u32 - 2;
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::MoveImmediate(1, 72),
+        Instruction::DirectStore(address, 1, 0),
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::MemoryOutOfBounds(address));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::DirectLoad(1, address, 0)]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::MemoryOutOfBounds(address));
+}
+
+#[test]
+fn invalid_syscall_traps() {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::PushImmediate(42),
+        Instruction::SystemCall
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::InvalidSyscall(42));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::PushImmediate(0), // length
+        Instruction::PushImmediate(0), // address
+        Instruction::PushImmediate(7), // file descriptor
+        Instruction::PushImmediate(1), // syscall number
+        Instruction::SystemCall
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::InvalidFileDescriptor(7));
+}
+
+#[test]
+fn halt_stops_early_with_exit_code() {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::MoveImmediate(1, 3),
+        Instruction::Exit(1),
+        Instruction::MoveImmediate(1, 4),
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap(), 3);
+    assert_eq!(vm.registers[1], 3);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::PushImmediate(5), // exit code
+        Instruction::PushImmediate(2), // syscall number
+        Instruction::SystemCall,
+        Instruction::Halt,
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap(), 5);
+}
+
+#[test]
+fn execute_for_stops_infinite_loop() {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::AddImmediate(1, 1, 1),
+        Instruction::JumpImmediate(1),
+    ]);
+    assert_eq!(vm.execute_for(10), ExecutionState::Running);
+    assert_eq!(vm.registers[1], 5);
+
+    assert_eq!(vm.execute_for(10), ExecutionState::Running);
+    assert_eq!(vm.registers[1], 10);
+}
+
+#[test]
+fn execute_for_uses_per_class_costs() {
+    let mut vm = VirtualMachine::new();
+    vm.fuel_costs = FuelCosts::uniform(1);
+    vm.fuel_costs.memory = 10;
+    vm.load_program(vec![
+        Instruction::MoveImmediate(1, 7),
+        Instruction::DirectStore(0, 1, 0),
+        Instruction::Exit(1),
+    ]);
+    assert_eq!(vm.execute_for(5), ExecutionState::Running);
+    assert_eq!(vm.program_counter, 2);
+    assert_eq!(vm.execute_for(11), ExecutionState::Halted(7));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::Pop(1)]);
+    assert!(matches!(vm.execute_for(5), ExecutionState::Trapped(_)));
+}
+
+#[test]
+fn config_sets_sizes_and_registers() {
+    let vm = VmConfig::new().memory_size(64).stack_size(2).register(3, 42).build();
+    assert_eq!(vm.memory.len(), 64);
+    assert_eq!(vm.registers[3], 42);
+
+    let mut vm = VmConfig::new().memory_size(64).build();
+    vm.load_program(vec![Instruction::DirectStore(64, 1, 3)]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::MemoryOutOfBounds(64));
+
+    let mut vm = VmConfig::new().stack_size(2).build();
+    vm.load_program(vec![
+        Instruction::PushImmediate(1),
+        Instruction::PushImmediate(2),
+        Instruction::PushImmediate(3),
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::StackOverflow);
+}
+
+#[test]
+fn call_depth_is_limited() {
+    let mut vm = VmConfig::new().max_call_depth(4).build();
+    vm.load_program(vec![Instruction::Call(1)]);
+    let trap = vm.execute_instruction_list().unwrap_err();
+    assert_eq!(trap.reason, TrapReason::CallStackOverflow);
+    assert_eq!(vm.call_stack.len(), 4);
+}
+
+#[test]
+fn string_table_is_read_only() {
+    let program = BFFProgram::new((0, 1, 0), vec![
+        Instruction::DirectLoad(1, 100, 3),
+        Instruction::DirectStore(101, 1, 3),
+    ], vec![(100, "hi".to_string())]);
+    let mut vm = VirtualMachine::new();
+    program.load_into(&mut vm).unwrap();
+
+    let trap = vm.execute_instruction_list().unwrap_err();
+    assert_eq!(trap.reason, TrapReason::ProtectionFault(101, Access::Write));
+    assert_eq!(vm.registers[1], 'h' as u32);
+    assert_eq!(vm.read_memory(100, 2).unwrap(), b"hi");
+}
+
+#[test]
+fn unmapped_memory_faults() {
+    let mut vm = VmConfig::new().memory_size(64).build();
+    vm.regions.clear();
+    vm.map_region(0, 32, Permissions::READ_WRITE).unwrap();
+    vm.map_region(16, 4, Permissions::NONE).unwrap();
+    vm.load_program(vec![
+        Instruction::DirectStore(12, 1, 0),
+        Instruction::DirectLoad(1, 18, 3),
+    ]);
+    assert_eq!(vm.execute_instruction_list().unwrap_err().reason, TrapReason::ProtectionFault(18, Access::Read));
+
+    assert_eq!(vm.write_memory(30, &[1, 2, 3]), Err(TrapReason::ProtectionFault(32, Access::Write)));
+    assert_eq!(vm.map_region(60, 8, Permissions::READ_ONLY), Err(TrapReason::MemoryOutOfBounds(60)));
+}
+
+#[test]
+fn snapshot_round_trips_and_resumes() {
+    let program = vec![
+        Instruction::MoveImmediate(1, 3),
+        Instruction::SubImmediate(1, 1, 1),
+        Instruction::Push(1),
+        Instruction::JumpNotZeroImmediate(1, 2),
+        Instruction::ExitImmediate(7),
+    ];
+    let mut vm = VirtualMachine::new();
+    vm.load_program(program.clone());
+    vm.map_region(10, 2, Permissions::READ_ONLY).unwrap();
+    vm.memory[0] = 9;
+    assert_eq!(vm.execute_for(4), ExecutionState::Running);
+
+    let bytes = vm.snapshot().to_bytes();
+    let snapshot = VmSnapshot::from_bytes(&bytes).unwrap();
+    assert_eq!(snapshot, vm.snapshot());
+
+    let mut resumed = VirtualMachine::new();
+    resumed.restore(snapshot);
+    assert_eq!(resumed.execute_instruction_list(), Ok(7));
+    assert_eq!(vm.execute_instruction_list(), Ok(7));
+    assert_eq!(resumed.snapshot(), vm.snapshot());
+    assert_eq!(resumed.memory[0], 9);
+    assert_eq!(resumed.permissions_at(11), Permissions::READ_ONLY);
+
+    assert_eq!(VmSnapshot::from_bytes(b"BFO0"), Err(SnapshotError::BadMagic));
+    assert_eq!(VmSnapshot::from_bytes(&bytes[..bytes.len() - 1]), Err(SnapshotError::Truncated));
+
+    let encoded = Instruction::MoveImmediate(1, 3).to_bfo_bytes();
+    let mut bad_register = bytes.clone();
+    let offset = bad_register.windows(encoded.len()).rposition(|window| window == encoded).unwrap();
+    bad_register[offset + 1] = 17;
+    assert_eq!(VmSnapshot::from_bytes(&bad_register), Err(SnapshotError::InvalidRegister(1, 17)));
+}
+
+#[test]
+fn program_sections_load_into_vm() {
+    let mut program = BFFProgram::new(VERSION, vec![
+        Instruction::ExitImmediate(1),
+        Instruction::DirectLoad(1, 16, 3),
+        Instruction::DirectStore(17, 1, 3),
+        Instruction::DirectStore(32, 1, 3),
+        Instruction::Exit(1),
+    ], vec![]);
+    program.data = vec![(16, vec![5, 6])];
+    program.bss = vec![(32, 4)];
+    program.entry = Some(2);
+
+    let mut vm = VirtualMachine::new();
+    vm.memory[33] = 9;
+    program.load_into(&mut vm).unwrap();
+    assert_eq!(vm.memory[33], 0);
+    assert_eq!(vm.execute_instruction_list(), Ok(5));
+    assert_eq!(vm.read_memory(16, 2).unwrap(), &[5, 5]);
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use bffcore::constants::instructions::Instruction;
+use bffcore::constants::types::{Address, Bits, Register};
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::{SymbolBinding, SymbolKind};
+
+pub struct Disassembler {}
+
+impl Disassembler {
+    // bffasm source that assembles back into the same bfo
+    pub fn disassemble(program: &BFFProgram) -> String {
+        let labels = Self::labels(program);
+        let mut output = String::new();
+
+        let (major, minor, patch) = program.version;
+        writeln!(output, ".version {}.{}.{}", major, minor, patch).unwrap();
+        if program.compact {
+            writeln!(output, ".compact").unwrap();
+        }
+        // without this the assembler would record the disassembly's own lines
+        if program.is_sectioned() && program.debug.is_empty() {
+            writeln!(output, ".strip_debug").unwrap();
+        }
+        for (address, string) in &program.string_table {
+            writeln!(output, "{} <- \"{}\"", address, escape_string(string)).unwrap();
+        }
+        for symbol in &program.symbols {
+            match symbol.binding {
+                SymbolBinding::Global => writeln!(output, ".global {}", symbol.name).unwrap(),
+                SymbolBinding::Extern => writeln!(output, ".extern {}", symbol.name).unwrap(),
+                SymbolBinding::Local => {}
+            }
+        }
+
+        // data labels are written where their address comes up, data is split around them
+        let mut data_labels: Vec<(Address, &str)> = program.symbols.iter()
+            .filter(|symbol| symbol.kind == SymbolKind::Data && symbol.binding != SymbolBinding::Extern)
+            .map(|symbol| (symbol.value, symbol.name.as_str()))
+            .collect();
+        let mut take_labels = |start: Address, end: Address| -> Vec<(Address, &str)> {
+            let (inside, outside) = data_labels.iter().partition(|(address, _)| *address >= start && *address < end);
+            data_labels = outside;
+            inside
+        };
+        let write_labels = |output: &mut String, labels: &[(Address, &str)], address: Address| {
+            for (_, name) in labels.iter().filter(|(label_address, _)| *label_address == address) {
+                writeln!(output, "{}:", name).unwrap();
+            }
+        };
+
+        for (address, bytes) in &program.data {
+            writeln!(output, ".data {}", address).unwrap();
+            let here = take_labels(*address, *address + bytes.len() as Address);
+            let mut splits: Vec<usize> = here.iter().map(|(label_address, _)| (label_address - address) as usize).collect();
+            splits.push(bytes.len());
+            splits.sort();
+            splits.dedup();
+
+            let mut start = 0;
+            for end in splits.into_iter().filter(|end| *end > 0) {
+                write_labels(&mut output, &here, address + start as Address);
+                for chunk in bytes[start..end].chunks(16) {
+                    let values: Vec<String> = chunk.iter().map(|byte| byte.to_string()).collect();
+                    writeln!(output, ".bytes {}", values.join(", ")).unwrap();
+                }
+                start = end;
+            }
+        }
+        for (address, size) in &program.bss {
+            writeln!(output, ".data {}", address).unwrap();
+            let here = take_labels(*address, *address + size);
+            let mut splits: Vec<Address> = here.iter().map(|(label_address, _)| label_address - address).collect();
+            splits.push(*size);
+            splits.sort();
+            splits.dedup();
+
+            let mut start = 0;
+            for end in splits.into_iter().filter(|end| *end > 0) {
+                write_labels(&mut output, &here, address + start);
+                writeln!(output, ".zero {}", end - start).unwrap();
+                start = end;
+            }
+        }
+        // labels outside of any data, on strings for example
+        let remaining = take_labels(0, Address::MAX);
+        for (address, name) in &remaining {
+            writeln!(output, ".data {}", address).unwrap();
+            writeln!(output, "{}:", name).unwrap();
+        }
+        if !program.data.is_empty() || !program.bss.is_empty() || !remaining.is_empty() {
+            writeln!(output, ".text").unwrap();
+        }
+        if let Some(entry) = program.entry {
+            writeln!(output, ".entry {}", target_name(&entry, &labels)).unwrap();
+        }
+        writeln!(output).unwrap();
+
+        let relocations: HashMap<usize, &str> = program.relocations.iter()
+            .map(|relocation| (relocation.index as usize, relocation.symbol.as_str()))
+            .collect();
+        for (index, instruction) in program.instructions.iter().enumerate() {
+            // labels are 1 based, the vm puts a nop before the first instruction
+            for label in labels.get(&(index + 1)).into_iter().flatten() {
+                writeln!(output, "{}:", label).unwrap();
+            }
+            let text = match relocations.get(&(index + 1)).and_then(|symbol| Self::disassemble_relocated(instruction, symbol)) {
+                Some(text) => text,
+                None => Self::disassemble_instruction(instruction, &labels),
+            };
+            writeln!(output, "    {}", text).unwrap();
+        }
+        for label in labels.get(&(program.instructions.len() + 1)).into_iter().flatten() {
+            writeln!(output, "{}:", label).unwrap();
+        }
+
+        // the original source locations, so traps and the debugger still point at the real source
+        if !program.debug.is_empty() {
+            writeln!(output, "\n; debug info").unwrap();
+        }
+        for (index, location) in &program.debug.locations {
+            write!(output, ".loc {} \"{}\" {} {}", index, escape_string(&location.file), location.line, location.column).unwrap();
+            match &location.label {
+                Some(label) => writeln!(output, " {}", label).unwrap(),
+                None => writeln!(output).unwrap(),
+            }
+        }
+        output
+    }
+
+    // an object's relocated operand is written as the symbol bffld fills in, None if the instruction has no such operand
+    pub fn disassemble_relocated(instruction: &Instruction, symbol: &str) -> Option<String> {
+        let text = match instruction {
+            Instruction::JumpImmediate(_) => format!("jmp {}", symbol),
+            Instruction::JumpNotZeroImmediate(condition, _) => format!("jnz {}, {}", reg(*condition), symbol),
+            Instruction::Call(_) => format!("call {}", symbol),
+            Instruction::MoveImmediate(dst, _) => format!("{} = &{}", reg(*dst), symbol),
+            Instruction::PushImmediate(_) => format!("push &{}", symbol),
+            Instruction::DirectStore(_, src, size) => format!("{} <- {} {}", symbol, reg(*src), size),
+            Instruction::DirectLoad(dst, _, size) => format!("{} <- {} {}", reg(*dst), symbol, size),
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    // sectioned files name their labels in the symbols section, anything else gets synthesized ones
+    pub fn labels(program: &BFFProgram) -> HashMap<usize, Vec<String>> {
+        if !program.is_sectioned() {
+            return Self::synthesize_labels(&program.instructions);
+        }
+        let mut labels: HashMap<usize, Vec<String>> = HashMap::new();
+        for symbol in &program.symbols {
+            if symbol.kind == SymbolKind::Code && symbol.binding != SymbolBinding::Extern {
+                labels.entry(symbol.value as usize).or_default().push(symbol.name.clone());
+            }
+        }
+        labels
+    }
+
+    // func_N for call targets and label_N for jump targets, N being the instruction index,
+    // targets outside the program are left as numbers
+    pub fn synthesize_labels(instructions: &[Instruction]) -> HashMap<usize, Vec<String>> {
+        let mut labels = HashMap::new();
+        let in_range = |target: Address| target >= 1 && target as usize <= instructions.len() + 1;
+
+        for instruction in instructions {
+            if let Instruction::Call(target) = instruction {
+                if in_range(*target) {
+                    labels.insert(*target as usize, vec![format!("func_{}", target)]);
+                }
+            }
+        }
+        for instruction in instructions {
+            match instruction {
+                Instruction::JumpImmediate(target) | Instruction::JumpNotZeroImmediate(_, target) if in_range(*target) => {
+                    labels.entry(*target as usize).or_insert_with(|| vec![format!("label_{}", target)]);
+                }
+                _ => {}
+            }
+        }
+        labels
+    }
+
+    pub fn disassemble_instruction(instruction: &Instruction, labels: &HashMap<usize, Vec<String>>) -> String {
+        let target = |address: &Address| target_name(address, labels);
+
+        match instruction {
+            Instruction::Nop => "nop".to_string(),
+
+            Instruction::Add(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "+", &reg(*rhs)),
+            Instruction::AddImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "+", &rhs.to_string()),
+            Instruction::Sub(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "-", &reg(*rhs)),
+            Instruction::SubImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "-", &rhs.to_string()),
+            Instruction::Mul(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "*", &reg(*rhs)),
+            Instruction::MulImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "*", &rhs.to_string()),
+            Instruction::Div(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "/", &reg(*rhs)),
+            Instruction::DivImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "/", &rhs.to_string()),
+            Instruction::Mod(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "%", &reg(*rhs)),
+            Instruction::ModImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "%", &rhs.to_string()),
+            Instruction::DivMod(div, rem, lhs, rhs) => div_mod(*div, *rem, 'u', *lhs, &reg(*rhs)),
+            Instruction::DivModImmediate(div, rem, lhs, rhs) => div_mod(*div, *rem, 'u', *lhs, &rhs.to_string()),
+
+            Instruction::GreaterThan(dst, lhs, rhs) => binary(*dst, 'u', *lhs, ">", &reg(*rhs)),
+            Instruction::GreaterThanImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, ">", &rhs.to_string()),
+            Instruction::LessThan(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "<", &reg(*rhs)),
+            Instruction::LessThanImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "<", &rhs.to_string()),
+            Instruction::GreaterThanOrEqual(dst, lhs, rhs) => binary(*dst, 'u', *lhs, ">=", &reg(*rhs)),
+            Instruction::GreaterThanOrEqualImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, ">=", &rhs.to_string()),
+            Instruction::LessThanOrEqual(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "<=", &reg(*rhs)),
+            Instruction::LessThanOrEqualImmediate(dst, lhs, rhs) => binary(*dst, 'u', *lhs, "<=", &rhs.to_string()),
+            Instruction::Equal(dst, lhs, rhs) => untyped(*dst, *lhs, "==", &reg(*rhs)),
+            Instruction::EqualImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, "==", &rhs.to_string()),
+            Instruction::NotEqual(dst, lhs, rhs) => untyped(*dst, *lhs, "!=", &reg(*rhs)),
+            Instruction::NotEqualImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, "!=", &rhs.to_string()),
+
+            Instruction::FloatAdd(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "+", &reg(*rhs)),
+            Instruction::FloatAddImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "+", &float(*rhs)),
+            Instruction::FloatSub(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "-", &reg(*rhs)),
+            Instruction::FloatSubImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "-", &float(*rhs)),
+            Instruction::FloatMul(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "*", &reg(*rhs)),
+            Instruction::FloatMulImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "*", &float(*rhs)),
+            Instruction::FloatDiv(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "/", &reg(*rhs)),
+            Instruction::FloatDivImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "/", &float(*rhs)),
+            Instruction::FloatMod(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "%", &reg(*rhs)),
+            Instruction::FloatModImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "%", &float(*rhs)),
+            Instruction::FloatDivMod(div, rem, lhs, rhs) => div_mod(*div, *rem, 'f', *lhs, &reg(*rhs)),
+            Instruction::FloatDivModImmediate(div, rem, lhs, rhs) => div_mod(*div, *rem, 'f', *lhs, &float(*rhs)),
+
+            Instruction::FloatGreaterThan(dst, lhs, rhs) => binary(*dst, 'f', *lhs, ">", &reg(*rhs)),
+            Instruction::FloatGreaterThanImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, ">", &float(*rhs)),
+            Instruction::FloatLessThan(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "<", &reg(*rhs)),
+            Instruction::FloatLessThanImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "<", &float(*rhs)),
+            Instruction::FloatGreaterThanOrEqual(dst, lhs, rhs) => binary(*dst, 'f', *lhs, ">=", &reg(*rhs)),
+            Instruction::FloatGreaterThanOrEqualImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, ">=", &float(*rhs)),
+            Instruction::FloatLessThanOrEqual(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "<=", &reg(*rhs)),
+            Instruction::FloatLessThanOrEqualImmediate(dst, lhs, rhs) => binary(*dst, 'f', *lhs, "<=", &float(*rhs)),
+            Instruction::FloatNegate(dst, src) => format!("{} = f - {}", reg(*dst), reg(*src)),
+            Instruction::FloatNegateImmediate(dst, value) => format!("{} = f - {}", reg(*dst), float(*value)),
+
+            Instruction::SignedAdd(dst, lhs, rhs) => binary(*dst, 's', *lhs, "+", &reg(*rhs)),
+            Instruction::SignedAddImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "+", &signed(*rhs)),
+            Instruction::SignedSub(dst, lhs, rhs) => binary(*dst, 's', *lhs, "-", &reg(*rhs)),
+            Instruction::SignedSubImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "-", &signed(*rhs)),
+            Instruction::SignedMul(dst, lhs, rhs) => binary(*dst, 's', *lhs, "*", &reg(*rhs)),
+            Instruction::SignedMulImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "*", &signed(*rhs)),
+            Instruction::SignedDiv(dst, lhs, rhs) => binary(*dst, 's', *lhs, "/", &reg(*rhs)),
+            Instruction::SignedDivImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "/", &signed(*rhs)),
+            Instruction::SignedMod(dst, lhs, rhs) => binary(*dst, 's', *lhs, "%", &reg(*rhs)),
+            Instruction::SignedModImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "%", &signed(*rhs)),
+            Instruction::SignedDivMod(div, rem, lhs, rhs) => div_mod(*div, *rem, 's', *lhs, &reg(*rhs)),
+            Instruction::SignedDivModImmediate(div, rem, lhs, rhs) => div_mod(*div, *rem, 's', *lhs, &signed(*rhs)),
+
+            Instruction::SignedGreaterThan(dst, lhs, rhs) => binary(*dst, 's', *lhs, ">", &reg(*rhs)),
+            Instruction::SignedGreaterThanImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, ">", &signed(*rhs)),
+            Instruction::SignedLessThan(dst, lhs, rhs) => binary(*dst, 's', *lhs, "<", &reg(*rhs)),
+            Instruction::SignedLessThanImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "<", &signed(*rhs)),
+            Instruction::SignedGreaterThanOrEqual(dst, lhs, rhs) => binary(*dst, 's', *lhs, ">=", &reg(*rhs)),
+            Instruction::SignedGreaterThanOrEqualImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, ">=", &signed(*rhs)),
+            Instruction::SignedLessThanOrEqual(dst, lhs, rhs) => binary(*dst, 's', *lhs, "<=", &reg(*rhs)),
+            Instruction::SignedLessThanOrEqualImmediate(dst, lhs, rhs) => binary(*dst, 's', *lhs, "<=", &signed(*rhs)),
+            Instruction::SignedNegate(dst, src) => format!("{} = s - {}", reg(*dst), reg(*src)),
+            Instruction::SignedNegateImmediate(dst, value) => format!("{} = s - {}", reg(*dst), signed(*value)),
+
+            Instruction::Not(dst, src) => format!("{} = !{}", reg(*dst), reg(*src)),
+            Instruction::NotImmediate(dst, value) => format!("{} = !{}", reg(*dst), value),
+            Instruction::And(dst, lhs, rhs) => untyped(*dst, *lhs, "&&", &reg(*rhs)),
+            Instruction::AndImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, "&&", &rhs.to_string()),
+            Instruction::Or(dst, lhs, rhs) => untyped(*dst, *lhs, "||", &reg(*rhs)),
+            Instruction::OrImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, "||", &rhs.to_string()),
+            Instruction::Xor(dst, lhs, rhs) => untyped(*dst, *lhs, "^^", &reg(*rhs)),
+            Instruction::XorImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, "^^", &rhs.to_string()),
+            Instruction::ShiftLeft(dst, lhs, rhs) => untyped(*dst, *lhs, "<<", &reg(*rhs)),
+            Instruction::ShiftLeftImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, "<<", &rhs.to_string()),
+            Instruction::ShiftRight(dst, lhs, rhs) => untyped(*dst, *lhs, ">>", &reg(*rhs)),
+            Instruction::ShiftRightImmediate(dst, lhs, rhs) => untyped(*dst, *lhs, ">>", &rhs.to_string()),
+
+            Instruction::Jump(address) => format!("jmp {}", reg(*address)),
+            Instruction::JumpImmediate(address) => format!("jmp {}", target(address)),
+            Instruction::JumpNotZero(condition, address) => format!("jnz {}, {}", reg(*condition), reg(*address)),
+            Instruction::JumpNotZeroImmediate(condition, address) => format!("jnz {}, {}", reg(*condition), target(address)),
+
+            Instruction::Move(dst, src) => format!("{} = {}", reg(*dst), reg(*src)),
+            Instruction::MoveImmediate(dst, value) => format!("{} = u {}", reg(*dst), value),
+
+            Instruction::Push(src) => format!("push {}", reg(*src)),
+            Instruction::PushImmediate(value) => format!("push {}", value),
+            Instruction::Pop(dst) => format!("pop {}", reg(*dst)),
+
+            Instruction::Store(dst, src, size) => format!("{} <- {} {}", reg(*dst), reg(*src), size),
+            Instruction::DirectStore(address, src, size) => format!("{} <- {} {}", address, reg(*src), size),
+            Instruction::Load(dst, src, size) => format!("{} <- [{}] {}", reg(*dst), reg(*src), size),
+            Instruction::DirectLoad(dst, address, size) => format!("{} <- {} {}", reg(*dst), address, size),
+
+            Instruction::Call(address) => format!("call {}", target(address)),
+            Instruction::Return => "ret".to_string(),
+
+            Instruction::SystemCall => "syscall".to_string(),
+
+            Instruction::Halt => "halt".to_string(),
+            Instruction::Exit(src) => format!("exit {}", reg(*src)),
+            Instruction::ExitImmediate(value) => format!("exit {}", value),
+        }
+    }
+}
+
+fn target_name(address: &Address, labels: &HashMap<usize, Vec<String>>) -> String {
+    match labels.get(&(*address as usize)).and_then(|names| names.first()) {
+        Some(label) => label.clone(),
+        None => address.to_string(),
+    }
+}
+
+fn reg(register: Register) -> String {
+    format!("reg{}", register)
+}
+
+fn binary(dst: Register, operation_type: char, lhs: Register, operator: &str, rhs: &str) -> String {
+    format!("{} = {} {} {} {}", reg(dst), operation_type, reg(lhs), operator, rhs)
+}
+
+fn untyped(dst: Register, lhs: Register, operator: &str, rhs: &str) -> String {
+    format!("{} = {} {} {}", reg(dst), reg(lhs), operator, rhs)
+}
+
+fn div_mod(div: Register, rem: Register, operation_type: char, lhs: Register, rhs: &str) -> String {
+    format!("{}, {} = {} {} /% {}", reg(div), reg(rem), operation_type, reg(lhs), rhs)
+}
+
+fn signed(value: Bits) -> String {
+    (value as i32).to_string()
+}
+
+// decimal when it survives the trip, the raw bits otherwise (nan and infinities)
+fn float(bits: Bits) -> String {
+    let value = f32::from_bits(bits);
+    if !value.is_finite() {
+        return format!("0f{:08x}", bits);
+    }
+    let mut text = value.to_string();
+    if !text.contains('.') {
+        text.push_str(".0");
+    }
+    text
+}
+
+fn escape_string(string: &str) -> String {
+    let mut escaped = String::new();
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
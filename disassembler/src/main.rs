@@ -0,0 +1,61 @@
+use std::collections::vec_deque::VecDeque;
+use bffcore::engine::bfo_reader::BFOReader;
+use bffdis::disassembler::Disassembler;
+
+fn main() {
+    let mut args: VecDeque<String> = std::env::args().collect();
+    let _path = args.pop_front().unwrap();
+    let mut output_path: Option<String> = None;
+
+    let file_path = match args.pop_front() {
+        Some(file_path) if !file_path.starts_with("-") => file_path,
+        _ => {
+            eprintln!("Usage: bffdis <file.bfo> [-o <file.bffasm>]");
+            std::process::exit(1);
+        }
+    };
+
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => {
+                output_path = match args.pop_front() {
+                    Some(path) => Some(path),
+                    None => {
+                        eprintln!("Error: '{}' expects a file path", current_arg);
+                        std::process::exit(1);
+                    }
+                };
+            }
+            unknown => {
+                eprintln!("Error: Unknown Argument: '{}'", unknown);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let contents = match std::fs::read(&file_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Error: Couldn't read '{}': {}", file_path, e);
+            std::process::exit(1);
+        }
+    };
+    let program = match BFOReader::read_program(contents) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Error: Couldn't disassemble '{}': {}", file_path, e);
+            std::process::exit(1);
+        }
+    };
+    let source = Disassembler::disassemble(&program);
+
+    match output_path {
+        Some(output_path) => {
+            if let Err(e) = std::fs::write(&output_path, source) {
+                eprintln!("Error: Couldn't write '{}': {}", output_path, e);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", source),
+    }
+}
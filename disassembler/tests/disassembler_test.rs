@@ -0,0 +1,153 @@
+use bffasm::AssemblerConfig;
+use bffcore::constants::constants::{INSTRUCTION_COUNT, INSTRUCTION_SIZE, VERSION};
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::bfo_reader::BFOReader;
+use bffcore::engine::program::BFFProgram;
+use bffdis::disassembler::Disassembler;
+
+fn assert_round_trips(program: &BFFProgram) {
+    let bytes = program.to_bfo_bytes();
+    let source = Disassembler::disassemble(&BFOReader::read_program(bytes.clone()).unwrap());
+    // a default build keeps the debug rows the disassembly lists, a stripped one only matches without any
+    for strip_debug in [false, true] {
+        if strip_debug && !program.debug.is_empty() {
+            continue;
+        }
+        let config = AssemblerConfig::new().object(program.object).strip_debug(strip_debug);
+        let reassembled = bffasm::assemble_with_config("<input>", &source, &config).unwrap_or_else(|e| panic!("{}\n{}", e, source));
+        assert_eq!(reassembled.to_bfo_bytes(), bytes, "\n{}", source);
+    }
+}
+
+#[test]
+fn every_opcode_round_trips() {
+    let mut instructions = vec![];
+    for opcode in 0..INSTRUCTION_COUNT as u8 {
+        let mut bytes = [0; INSTRUCTION_SIZE as usize];
+        bytes[0] = opcode;
+        bytes[1] = 1;
+        bytes[2] = 2;
+        bytes[3] = 3;
+        instructions.push(Instruction::from_bfo_bytes(bytes).unwrap());
+    }
+    assert_round_trips(&BFFProgram::new((0, 1, 0), instructions, vec![]));
+}
+
+#[test]
+fn immediates_targets_and_strings_round_trip() {
+    let program = BFFProgram::new(VERSION, vec![
+        Instruction::FloatAddImmediate(1, 2, 1.5f32.to_bits()),
+        Instruction::FloatSubImmediate(1, 2, f32::NAN.to_bits()),
+        Instruction::FloatMulImmediate(1, 2, f32::NEG_INFINITY.to_bits()),
+        Instruction::FloatDivImmediate(1, 2, (-0.0f32).to_bits()),
+        Instruction::FloatNegateImmediate(1, 1e30f32.to_bits()),
+        Instruction::SignedAddImmediate(1, 2, -5i32 as u32),
+        Instruction::SignedNegateImmediate(1, i32::MIN as u32),
+        Instruction::MoveImmediate(1, u32::MAX),
+        Instruction::Load(1, 2, 2),
+        Instruction::Store(1, 2, 2),
+        Instruction::JumpImmediate(1),
+        Instruction::JumpNotZeroImmediate(3, 14),
+        Instruction::JumpImmediate(0),
+        Instruction::Call(500),
+        Instruction::Call(3),
+    ], vec![
+        (0, "hi \"there\"\n\t\\".to_string()),
+        (64, "caf\u{e9} \u{1}\u{7f}".to_string()),
+        (128, String::new()),
+    ]);
+    assert_round_trips(&program);
+}
+
+#[test]
+fn data_and_bss_round_trip() {
+    let mut program = BFFProgram::new(VERSION, vec![Instruction::Halt], vec![(0, "hi".to_string())]);
+    program.data = vec![(16, (0..=255).collect()), (272, vec![1]), (512, vec![9, 9])];
+    program.bss = vec![(273, 15), (1024, 4)];
+    assert_round_trips(&program);
+
+    program.compact = true;
+    assert_round_trips(&program);
+}
+
+#[test]
+fn objects_round_trip() {
+    let source = "\
+.global main
+.extern print
+.data 64
+count:
+.word 3
+.zero 4
+buffer:
+.zero 8
+.data 200
+name:
+.text
+main:
+reg1 <- count 2
+push &buffer
+reg2 = &main
+call print
+jnz reg1, main
+buffer <- reg1 2
+ret
+";
+    let program = bffasm::assemble_with_config("<input>", source, &AssemblerConfig::new().object(true)).unwrap();
+    assert!(!program.debug.is_empty());
+    assert!(!program.relocations.is_empty());
+    assert_round_trips(&program);
+}
+
+#[test]
+fn assembled_source_round_trips() {
+    let source = "\
+0 <- \"Hello, World!\\n\"
+.entry start
+start:
+reg1 = u 10
+loop:
+reg1 = u reg1 - 1
+reg2 = reg1 == 5
+jnz reg2, done
+call print
+jnz reg1, loop
+done:
+exit reg1
+print:
+push 14
+push 0
+push 0
+push 1
+syscall
+ret
+";
+    let program = bffasm::assemble_with_config("<input>", source, &AssemblerConfig::new()).unwrap();
+    assert!(!program.debug.is_empty());
+    assert_round_trips(&program);
+    assert_round_trips(&bffasm::assemble_with_config("<input>", source, &AssemblerConfig::new().strip_debug(true)).unwrap());
+    assert_eq!(bffasm::assemble(".version 0.2.7\nhalt").unwrap().version, (0, 2, 7));
+}
+
+#[test]
+fn synthesizes_labels() {
+    let program = BFFProgram::new((0, 1, 0), vec![
+        Instruction::Call(4),
+        Instruction::JumpNotZeroImmediate(1, 1),
+        Instruction::Halt,
+        Instruction::JumpImmediate(5),
+    ], vec![(0, "hi".to_string())]);
+
+    assert_eq!(Disassembler::disassemble(&program), "\
+.version 0.1.0
+0 <- \"hi\"
+
+label_1:
+    call func_4
+    jnz reg1, label_1
+    halt
+func_4:
+    jmp label_5
+label_5:
+");
+}
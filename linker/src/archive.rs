@@ -0,0 +1,172 @@
+use std::fmt;
+use bffcore::engine::bfo_reader::{BFOReader, BfoError};
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::SymbolBinding;
+
+pub const MAGIC: &[u8; 4] = b"BFFA";
+pub const ARCHIVE_VERSION: u16 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArchiveError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated(usize), // offset
+    NotAnObject(String), // member name
+    DuplicateMember(String),
+    BadMemberName(String), // has to be a plain file name, `bffar extract` writes members next to each other
+    StaleIndex, // the stored symbol index doesn't match the members
+    Member { member: String, error: BfoError },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::BadMagic => write!(f, "not a bff archive"),
+            ArchiveError::UnsupportedVersion(version) => write!(f, "unsupported archive version {}", version),
+            ArchiveError::Truncated(offset) => write!(f, "truncated archive at offset {}", offset),
+            ArchiveError::NotAnObject(member) => write!(f, "'{}' isn't an object, assemble it with bffasm -c", member),
+            ArchiveError::DuplicateMember(member) => write!(f, "the archive already has a member named '{}'", member),
+            ArchiveError::BadMemberName(member) => write!(f, "'{}' isn't a valid member name", member),
+            ArchiveError::StaleIndex => write!(f, "the symbol index doesn't match the members, recreate the archive with bffar"),
+            ArchiveError::Member { member, error } => write!(f, "member '{}': {}", member, error),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+// a library of objects, bffld only links the members that define a symbol something else needs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Archive {
+    members: Vec<(String, BFFProgram)>,
+    index: Vec<(String, u32)>, // kept up to date by `add`, see `index`
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_archive(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn add(&mut self, name: &str, object: BFFProgram) -> Result<(), ArchiveError> {
+        if !object.object {
+            return Err(ArchiveError::NotAnObject(name.to_string()));
+        }
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(ArchiveError::BadMemberName(name.to_string()));
+        }
+        if self.members.iter().any(|(member, _)| member == name) {
+            return Err(ArchiveError::DuplicateMember(name.to_string()));
+        }
+        for symbol in object.symbols.iter().filter(|symbol| symbol.binding == SymbolBinding::Global) {
+            if !self.index.iter().any(|(name, _)| *name == symbol.name) {
+                self.index.push((symbol.name.clone(), self.members.len() as u32));
+            }
+        }
+        self.members.push((name.to_string(), object));
+        Ok(())
+    }
+
+    pub fn members(&self) -> &[(String, BFFProgram)] {
+        &self.members
+    }
+
+    // every global symbol and the index of the member defining it, the first member wins if there are several
+    pub fn index(&self) -> &[(String, u32)] {
+        &self.index
+    }
+
+    pub fn find(&self, symbol: &str) -> Option<usize> {
+        self.index.iter()
+            .find(|(name, _)| name == symbol)
+            .map(|(_, member)| *member as usize)
+    }
+
+    // magic, u16 version, u16 reserved, the symbol index as u32 count and (u32 member, u16 length, name),
+    // then u32 member count and (u16 length, name, u32 length, bfo bytes) per member
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+
+        bytes.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        for (name, member) in &self.index {
+            bytes.extend_from_slice(&member.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.members.len() as u32).to_le_bytes());
+        for (name, object) in &self.members {
+            let object = object.to_bfo_bytes();
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(object.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&object);
+        }
+        bytes
+    }
+
+    // the stored index has to be the one the members give, so a hand edited archive can't point bffld at the wrong member
+    pub fn from_bytes(bytes: &[u8]) -> Result<Archive, ArchiveError> {
+        if !Self::is_archive(bytes) {
+            return Err(ArchiveError::BadMagic);
+        }
+        let mut reader = Reader { bytes, offset: MAGIC.len() };
+        let version = reader.u16()?;
+        if version != ARCHIVE_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(version));
+        }
+        reader.u16()?;
+
+        let index_count = reader.u32()?;
+        let mut index = vec![];
+        for _ in 0..index_count {
+            let member = reader.u32()?;
+            let length = reader.u16()? as usize;
+            index.push((String::from_utf8_lossy(reader.take(length)?).to_string(), member));
+        }
+
+        let mut archive = Archive::new();
+        let member_count = reader.u32()?;
+        for _ in 0..member_count {
+            let length = reader.u16()? as usize;
+            let name = String::from_utf8_lossy(reader.take(length)?).to_string();
+            let length = reader.u32()? as usize;
+            let object = BFOReader::read_program(reader.take(length)?.to_vec())
+                .map_err(|error| ArchiveError::Member { member: name.clone(), error })?;
+            archive.add(&name, object)?;
+        }
+        if archive.index != index {
+            return Err(ArchiveError::StaleIndex);
+        }
+        Ok(archive)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ArchiveError> {
+        let bytes = self.offset.checked_add(length)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or(ArchiveError::Truncated(self.offset))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, ArchiveError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ArchiveError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
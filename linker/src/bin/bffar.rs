@@ -0,0 +1,104 @@
+use std::collections::vec_deque::VecDeque;
+use std::path::Path;
+use bffcore::engine::bfo_reader::BFOReader;
+use bffcore::engine::section::SymbolBinding;
+use bffld::archive::Archive;
+
+const USAGE: &str = "Usage: bffar create <lib.bfa> <file.o>... | bffar list <lib.bfa> | bffar extract <lib.bfa> [member]... [-C <dir>]";
+
+fn main() {
+    let mut args: VecDeque<String> = std::env::args().collect();
+    let _path = args.pop_front().unwrap();
+    let command = args.pop_front().unwrap_or_else(|| fail(USAGE));
+    let archive_path = match args.pop_front() {
+        Some(archive_path) if !archive_path.starts_with("-") => archive_path,
+        _ => fail(USAGE),
+    };
+
+    match &*command {
+        "create" => create(&archive_path, args),
+        "list" => list(&archive_path, args),
+        "extract" => extract(&archive_path, args),
+        unknown => fail(&format!("Unknown Command: '{}'\n{}", unknown, USAGE)),
+    }
+}
+
+// members are named after the object's file name, without its directory
+fn create(archive_path: &str, args: VecDeque<String>) {
+    if args.is_empty() {
+        fail(USAGE);
+    }
+    let mut archive = Archive::new();
+    for input in args {
+        let object = match BFOReader::read_file_from_path(input.clone()) {
+            Ok(object) => object,
+            Err(e) => fail(&format!("Couldn't read '{}': {}", input, e)),
+        };
+        let name = Path::new(&input).file_name().map_or(input.clone(), |name| name.to_string_lossy().to_string());
+        if let Err(e) = archive.add(&name, object) {
+            fail(&format!("Couldn't add '{}': {}", input, e));
+        }
+    }
+    if let Err(e) = std::fs::write(archive_path, archive.to_bytes()) {
+        fail(&format!("Couldn't write '{}': {}", archive_path, e));
+    }
+}
+
+// each member and the global symbols it defines
+fn list(archive_path: &str, args: VecDeque<String>) {
+    if let Some(unknown) = args.front() {
+        fail(&format!("Unknown Argument: '{}'", unknown));
+    }
+    let archive = read_archive(archive_path);
+    for (name, object) in archive.members() {
+        println!("{}", name);
+        for symbol in object.symbols.iter().filter(|symbol| symbol.binding == SymbolBinding::Global) {
+            println!("    {}", symbol.name);
+        }
+    }
+}
+
+// every member unless some are named
+fn extract(archive_path: &str, mut args: VecDeque<String>) {
+    let mut directory = ".".to_string();
+    let mut wanted: Vec<String> = vec![];
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-C" => directory = args.pop_front().unwrap_or_else(|| fail("'-C' expects a directory")),
+            unknown if unknown.starts_with("-") => fail(&format!("Unknown Argument: '{}'", unknown)),
+            member => wanted.push(member.to_string()),
+        }
+    }
+
+    let archive = read_archive(archive_path);
+    for member in &wanted {
+        if !archive.members().iter().any(|(name, _)| name == member) {
+            fail(&format!("'{}' has no member '{}'", archive_path, member));
+        }
+    }
+    for (name, object) in archive.members() {
+        if !wanted.is_empty() && !wanted.contains(name) {
+            continue;
+        }
+        let path = Path::new(&directory).join(name);
+        if let Err(e) = std::fs::write(&path, object.to_bfo_bytes()) {
+            fail(&format!("Couldn't write '{}': {}", path.display(), e));
+        }
+    }
+}
+
+fn read_archive(archive_path: &str) -> Archive {
+    let contents = match std::fs::read(archive_path) {
+        Ok(contents) => contents,
+        Err(e) => fail(&format!("Couldn't read '{}': {}", archive_path, e)),
+    };
+    match Archive::from_bytes(&contents) {
+        Ok(archive) => archive,
+        Err(e) => fail(&format!("Couldn't read '{}': {}", archive_path, e)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
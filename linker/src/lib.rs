@@ -0,0 +1,2 @@
+pub mod linker;
+pub mod archive;
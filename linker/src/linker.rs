@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use bffcore::constants::constants::VERSION;
+use bffcore::constants::types::Address;
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::{Symbol, SymbolBinding, SymbolKind};
+use crate::archive::Archive;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkError {
+    NotAnObject(String), // object name
+    DuplicateSymbol { symbol: String, first: String, second: String },
+    UndefinedSymbol { symbol: String, object: String },
+    DuplicateEntry { first: String, second: String },
+    OverlappingData { address: Address, first: String, second: String },
+    BadRelocation { object: String, index: Address }, // points past the code or at an instruction without an address operand
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::NotAnObject(object) => write!(f, "'{}' isn't an object, assemble it with bffasm -c", object),
+            LinkError::DuplicateSymbol { symbol, first, second } => write!(f, "'{}' is defined as '.global' in both '{}' and '{}'", symbol, first, second),
+            LinkError::UndefinedSymbol { symbol, object } => write!(f, "undefined symbol '{}' in '{}'", symbol, object),
+            LinkError::DuplicateEntry { first, second } => write!(f, "both '{}' and '{}' have an '.entry'", first, second),
+            LinkError::OverlappingData { address, first, second } => write!(f, "data of '{}' and '{}' overlap at address {}", first, second, address),
+            LinkError::BadRelocation { object, index } => write!(f, "relocation for instruction {} in '{}' has no address to fill in", index, object),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+pub struct Linker {}
+
+impl Linker {
+    // appends the code of every object in order, data keeps its addresses. symbols are looked up in the
+    // object itself first, then in the `.global`s of every object
+    pub fn link(objects: &[(String, BFFProgram)]) -> Result<BFFProgram, LinkError> {
+        let mut bases = vec![];
+        let mut base = 0;
+        for (name, object) in objects {
+            if !object.object {
+                return Err(LinkError::NotAnObject(name.clone()));
+            }
+            bases.push(base);
+            base += object.instructions.len() as Address;
+        }
+
+        let value = |symbol: &Symbol, base: Address| match symbol.kind {
+            SymbolKind::Code => symbol.value + base,
+            SymbolKind::Data => symbol.value,
+        };
+
+        let mut globals: HashMap<&str, (Address, &str)> = HashMap::new();
+        for ((name, object), base) in objects.iter().zip(&bases) {
+            for symbol in object.symbols.iter().filter(|symbol| symbol.binding == SymbolBinding::Global) {
+                if let Some((_, first)) = globals.insert(&symbol.name, (value(symbol, *base), name)) {
+                    return Err(LinkError::DuplicateSymbol { symbol: symbol.name.clone(), first: first.to_string(), second: name.clone() });
+                }
+            }
+        }
+
+        Self::check_data(objects)?;
+
+        let mut program = BFFProgram::new(VERSION, vec![], vec![]);
+        program.compact = objects.iter().all(|(_, object)| object.compact);
+        let mut entry_object: Option<&str> = None;
+
+        for ((name, object), base) in objects.iter().zip(&bases) {
+            let mut instructions = object.instructions.clone();
+            for relocation in &object.relocations {
+                let local = object.symbols.iter()
+                    .find(|symbol| symbol.name == relocation.symbol && symbol.binding != SymbolBinding::Extern);
+                let address = match (local, globals.get(&*relocation.symbol)) {
+                    (Some(symbol), _) => value(symbol, *base),
+                    (None, Some((address, _))) => *address,
+                    (None, None) => return Err(LinkError::UndefinedSymbol { symbol: relocation.symbol.clone(), object: name.clone() }),
+                };
+
+                let bad_relocation = || LinkError::BadRelocation { object: name.clone(), index: relocation.index };
+                let instruction = (relocation.index as usize).checked_sub(1)
+                    .and_then(|index| instructions.get_mut(index))
+                    .ok_or_else(bad_relocation)?;
+                *instruction = instruction.with_address(address).ok_or_else(bad_relocation)?;
+            }
+            program.instructions.extend(instructions);
+
+            program.string_table.extend(object.string_table.iter().cloned());
+            program.data.extend(object.data.iter().cloned());
+            program.bss.extend(object.bss.iter().cloned());
+            if let Some(entry) = object.entry {
+                if let Some(first) = entry_object {
+                    return Err(LinkError::DuplicateEntry { first: first.to_string(), second: name.clone() });
+                }
+                entry_object = Some(name);
+                program.entry = Some(entry + base);
+            }
+            // local symbols only mean something inside their object and would clash between objects,
+            // the debug rows keep their names for traps
+            for symbol in object.symbols.iter().filter(|symbol| symbol.binding == SymbolBinding::Global) {
+                program.symbols.push(Symbol::new(&symbol.name, value(symbol, *base), symbol.kind, symbol.binding));
+            }
+            for (index, location) in &object.debug.locations {
+                program.debug.insert(index + base, location.clone());
+            }
+        }
+        Ok(program)
+    }
+
+    // like `link`, adding the archive members that define a symbol the linked objects need, and the members
+    // those need in turn. archives are searched in order, members that aren't needed are left out
+    pub fn link_with_archives(objects: &[(String, BFFProgram)], archives: &[(String, Archive)]) -> Result<BFFProgram, LinkError> {
+        let mut objects = objects.to_vec();
+        let mut pulled: Vec<(usize, usize)> = vec![];
+
+        loop {
+            let defined: HashSet<&str> = objects.iter()
+                .flat_map(|(_, object)| object.symbols.iter())
+                .filter(|symbol| symbol.binding == SymbolBinding::Global)
+                .map(|symbol| symbol.name.as_str())
+                .collect();
+            let mut needed = objects.iter().flat_map(|(_, object)| {
+                object.relocations.iter()
+                    .map(|relocation| relocation.symbol.as_str())
+                    .filter(|name| !object.symbols.iter().any(|symbol| symbol.name == *name && symbol.binding != SymbolBinding::Extern))
+            }).filter(|name| !defined.contains(name));
+
+            let member = needed.find_map(|name| {
+                archives.iter().enumerate().find_map(|(archive_index, (_, archive))| {
+                    archive.find(name).map(|member_index| (archive_index, member_index))
+                })
+            });
+            match member {
+                Some(member) if !pulled.contains(&member) => {
+                    let (archive_name, archive) = &archives[member.0];
+                    let (member_name, object) = &archive.members()[member.1];
+                    objects.push((format!("{}({})", archive_name, member_name), object.clone()));
+                    pulled.push(member);
+                }
+                _ => break,
+            }
+        }
+        Self::link(&objects)
+    }
+
+    // strings, data and bss of different objects can't share memory since none of it is moved
+    fn check_data(objects: &[(String, BFFProgram)]) -> Result<(), LinkError> {
+        let mut ranges: Vec<(Address, Address, usize)> = vec![];
+        for (object_index, (_, object)) in objects.iter().enumerate() {
+            let strings = object.string_table.iter().map(|(address, string)| (*address, string.len() as Address));
+            let data = object.data.iter().map(|(address, bytes)| (*address, bytes.len() as Address));
+            let bss = object.bss.iter().copied();
+            for (address, length) in strings.chain(data).chain(bss).filter(|(_, length)| *length > 0) {
+                ranges.push((address, address + length, object_index));
+            }
+        }
+        ranges.sort();
+
+        for (index, (_, end, first)) in ranges.iter().enumerate() {
+            for (other_start, _, second) in &ranges[index + 1..] {
+                if other_start >= end {
+                    break;
+                }
+                if first != second {
+                    // named in the order the objects were given
+                    let (first, second) = (first.min(second), first.max(second));
+                    return Err(LinkError::OverlappingData { address: *other_start, first: objects[*first].0.clone(), second: objects[*second].0.clone() });
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::vec_deque::VecDeque;
+use bffcore::engine::bfo_reader::BFOReader;
+use bffld::archive::Archive;
+use bffld::linker::Linker;
+
+fn main() {
+    let mut args: VecDeque<String> = std::env::args().collect();
+    let _path = args.pop_front().unwrap();
+    let mut output_path = "a.bfo".to_string();
+    let mut inputs: Vec<String> = vec![];
+
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => {
+                output_path = match args.pop_front() {
+                    Some(path) => path,
+                    None => {
+                        eprintln!("Error: '{}' expects a file path", current_arg);
+                        std::process::exit(1);
+                    }
+                };
+            }
+            unknown if unknown.starts_with("-") => {
+                eprintln!("Error: Unknown Argument: '{}'", unknown);
+                std::process::exit(1);
+            }
+            input => inputs.push(input.to_string()),
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!("Usage: bffld <file.o | file.bfa>... [-o <file.bfo>]");
+        std::process::exit(1);
+    }
+
+    let mut objects = vec![];
+    let mut archives = vec![];
+    for input in inputs {
+        let contents = match std::fs::read(&input) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Error: Couldn't read '{}': {}", input, e);
+                std::process::exit(1);
+            }
+        };
+        if Archive::is_archive(&contents) {
+            match Archive::from_bytes(&contents) {
+                Ok(archive) => archives.push((input, archive)),
+                Err(e) => {
+                    eprintln!("Error: Couldn't read '{}': {}", input, e);
+                    std::process::exit(1);
+                }
+            }
+            continue;
+        }
+        match BFOReader::read_program(contents) {
+            Ok(object) => objects.push((input, object)),
+            Err(e) => {
+                eprintln!("Error: Couldn't read '{}': {}", input, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let program = match Linker::link_with_archives(&objects, &archives) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = std::fs::write(&output_path, program.to_bfo_bytes()) {
+        eprintln!("Error: Couldn't write '{}': {}", output_path, e);
+        std::process::exit(1);
+    }
+}
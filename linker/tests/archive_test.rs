@@ -0,0 +1,62 @@
+use bffcore::engine::program::BFFProgram;
+use bffld::archive::{Archive, ArchiveError};
+use bffld::linker::{LinkError, Linker};
+
+fn object(name: &str, source: &str) -> (String, BFFProgram) {
+    (name.to_string(), bffasm::assemble_object(name, source).unwrap())
+}
+
+fn library() -> Archive {
+    let mut archive = Archive::new();
+    for (name, object) in [
+        object("double.o", ".global double\n.extern add\ndouble:\nreg2 = reg1\ncall add\nret"),
+        object("add.o", ".global add\nadd:\nreg1 = u reg1 + reg2\nret"),
+        object("unused.o", ".global unused\n.data 0\n.zero 4096\n.text\nunused:\nret"),
+    ] {
+        archive.add(&name, object).unwrap();
+    }
+    archive
+}
+
+#[test]
+fn archives_round_trip() {
+    let archive = library();
+    let read = Archive::from_bytes(&archive.to_bytes()).unwrap();
+    assert_eq!(read, archive);
+    assert_eq!(read.find("add"), Some(1));
+    assert_eq!(read.find("missing"), None);
+
+    // bffld trusts the stored index, so it has to agree with the members
+    let mut stale = archive.to_bytes();
+    assert_eq!(&stale[18..24], b"double");
+    stale[12] = 2;
+    assert_eq!(Archive::from_bytes(&stale), Err(ArchiveError::StaleIndex));
+
+    let mut archive = library();
+    let (name, object) = object("add.o", ".global add\nadd:\nret");
+    assert_eq!(archive.add(&name, object.clone()), Err(ArchiveError::DuplicateMember("add.o".to_string())));
+    assert_eq!(archive.add("../add.o", object), Err(ArchiveError::BadMemberName("../add.o".to_string())));
+    assert_eq!(archive.add("main.bfo", bffasm::assemble("halt").unwrap()), Err(ArchiveError::NotAnObject("main.bfo".to_string())));
+    assert_eq!(Archive::from_bytes(b"BFFA"), Err(ArchiveError::Truncated(4)));
+    assert_eq!(Archive::from_bytes(b"nope"), Err(ArchiveError::BadMagic));
+}
+
+#[test]
+fn only_needed_members_are_linked() {
+    let main = object("main.o", ".extern double\n.entry start\nstart:\nreg1 = u 21\ncall double\nexit reg1");
+    let archives = [("lib.bfa".to_string(), library())];
+    let mut program = Linker::link_with_archives(&[main], &archives).unwrap();
+
+    // double pulls in add, nothing needs unused or its data
+    assert!(program.symbols.iter().any(|symbol| symbol.name == "add"));
+    assert!(!program.symbols.iter().any(|symbol| symbol.name == "unused"));
+    assert!(program.bss.is_empty());
+    assert_eq!(program.instructions.len(), 3 + 3 + 2);
+    assert_eq!(program.execute().unwrap(), 42);
+
+    let lonely = object("lonely.o", ".extern missing\ncall missing");
+    assert_eq!(
+        Linker::link_with_archives(&[lonely], &archives),
+        Err(LinkError::UndefinedSymbol { symbol: "missing".to_string(), object: "lonely.o".to_string() }),
+    );
+}
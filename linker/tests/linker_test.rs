@@ -0,0 +1,93 @@
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::bfo_reader::BFOReader;
+use bffcore::engine::program::BFFProgram;
+use bffld::linker::{LinkError, Linker};
+
+fn object(name: &str, source: &str) -> (String, BFFProgram) {
+    let object = bffasm::assemble_object(name, source).unwrap();
+    // objects go through a file between bffasm and bffld
+    (name.to_string(), BFOReader::read_program(object.to_bfo_bytes()).unwrap())
+}
+
+const MAIN: &str = "\
+.global start
+.extern add_one
+.extern counter
+.entry start
+start:
+reg1 = u 41
+call add_one
+reg2 <- counter 2
+reg1 = u reg1 + reg2
+exit reg1
+";
+
+const LIBRARY: &str = "\
+.global add_one
+.global counter
+.data 100
+counter:
+.word 1
+.text
+add_one:
+jmp increment
+increment:
+reg1 = u reg1 + 1
+ret
+";
+
+#[test]
+fn links_objects_into_a_program() {
+    let program = Linker::link(&[object("main.o", MAIN), object("lib.o", LIBRARY)]).unwrap();
+    assert!(!program.object);
+    assert_eq!(program.entry, Some(1));
+    assert_eq!(program.instructions[1], Instruction::Call(6));
+    assert_eq!(program.instructions[2], Instruction::DirectLoad(2, 100, 2));
+    // the library's own jump moved with its code
+    assert_eq!(program.instructions[5], Instruction::JumpImmediate(7));
+    assert_eq!(program.debug.lookup(7).unwrap().file, "lib.o");
+
+    let mut program = BFOReader::read_program(program.to_bfo_bytes()).unwrap();
+    assert_eq!(program.execute().unwrap(), 43);
+}
+
+#[test]
+fn local_labels_stay_in_their_object() {
+    let main = ".global start\n.extern count\n.entry start\nstart:\nreg1 = u 2\nloop:\nreg1 = u reg1 - 1\ncall count\njnz reg1, loop\nexit reg2\n";
+    let library = ".global count\ncount:\nreg3 = u 3\nloop:\nreg2 = u reg2 + 1\nreg3 = u reg3 - 1\njnz reg3, loop\nret\n";
+    let program = Linker::link(&[object("main.o", main), object("lib.o", library)]).unwrap();
+    let names: Vec<&str> = program.symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+    assert_eq!(names, vec!["start", "count"]);
+    assert_eq!(program.debug.lookup(8).unwrap().label.as_deref(), Some("loop"));
+
+    let mut program = BFOReader::read_program(program.to_bfo_bytes()).unwrap();
+    assert_eq!(program.execute().unwrap(), 6);
+}
+
+#[test]
+fn reports_link_errors() {
+    let error = |objects: &[(String, BFFProgram)]| Linker::link(objects).unwrap_err();
+
+    assert_eq!(error(&[object("main.o", MAIN)]), LinkError::UndefinedSymbol { symbol: "add_one".to_string(), object: "main.o".to_string() });
+    assert_eq!(
+        error(&[object("lib.o", LIBRARY), object("copy.o", LIBRARY)]),
+        LinkError::DuplicateSymbol { symbol: "add_one".to_string(), first: "lib.o".to_string(), second: "copy.o".to_string() },
+    );
+    assert_eq!(
+        error(&[object("lib.o", LIBRARY), object("data.o", ".data 102\n.zero 4")]),
+        LinkError::OverlappingData { address: 102, first: "lib.o".to_string(), second: "data.o".to_string() },
+    );
+    assert_eq!(
+        error(&[object("a.o", ".entry a\na:\nhalt"), object("b.o", ".entry b\nb:\nhalt")]),
+        LinkError::DuplicateEntry { first: "a.o".to_string(), second: "b.o".to_string() },
+    );
+    let program = bffasm::assemble("halt").unwrap();
+    assert_eq!(error(&[("halt.bfo".to_string(), program)]), LinkError::NotAnObject("halt.bfo".to_string()));
+}
+
+#[test]
+fn externs_need_an_object() {
+    assert!(bffasm::assemble(".extern print\ncall print").is_err());
+    assert!(bffasm::assemble("call print").is_err());
+    assert!(bffasm::assemble_object("a.o", ".global missing\nhalt").is_err());
+}
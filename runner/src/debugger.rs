@@ -0,0 +1,357 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::io::{BufRead, Write};
+use bffcore::constants::constants::STACK_POINTER;
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::debug_info::DebugInfo;
+use bffcore::engine::virtual_machine::{ExecutionState, VirtualMachine};
+
+const HELP: &str = "\
+commands:
+  break <index|label>    set a breakpoint            (b)
+  clear <index|label>    clear a breakpoint
+  breakpoints            list breakpoints            (bl)
+  step [n]               execute n instructions      (s)
+  next                   step over a call            (n)
+  finish                 run until the current function returns
+  continue               run until a breakpoint      (c)
+  list [n]               show instructions around pc (l)
+  registers              print registers             (r)
+  memory <addr> [len]    hex dump memory             (x)
+  stack                  show the value stack
+  callstack              show the call stack         (bt)
+  quit                   stop debugging              (q)";
+
+// the debugger's output is best effort, a closed stdout isn't a reason to stop the program
+macro_rules! say {
+    ($debugger: expr, $($arg: tt)*) => {
+        { writeln!($debugger.output, $($arg)*).ok(); }
+    };
+}
+
+enum Stop {
+    Paused,
+    Finished,
+}
+
+pub struct Debugger<'a, W: Write = io::Stdout> {
+    pub vm: &'a mut VirtualMachine,
+    pub breakpoints: BTreeSet<usize>,
+    pub labels: HashMap<String, usize>,
+    pub debug_info: DebugInfo,
+    pub output: W,
+    trap: Option<ExecutionState>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(vm: &'a mut VirtualMachine, labels: HashMap<String, usize>, debug_info: DebugInfo) -> Self {
+        Debugger::with_output(vm, labels, debug_info, io::stdout())
+    }
+
+    // reads commands from stdin until `quit`, the end of the input, or the end of the program
+    pub fn run(&mut self) -> ExecutionState {
+        self.run_with(io::stdin().lock())
+    }
+}
+
+impl<'a, W: Write> Debugger<'a, W> {
+    pub fn with_output(vm: &'a mut VirtualMachine, labels: HashMap<String, usize>, debug_info: DebugInfo, output: W) -> Self {
+        Self {
+            vm,
+            breakpoints: BTreeSet::new(),
+            labels,
+            debug_info,
+            output,
+            trap: None,
+        }
+    }
+
+    // like `run`, with the commands coming from `input`
+    pub fn run_with<R: BufRead>(&mut self, mut input: R) -> ExecutionState {
+        say!(self, "bff debugger, type 'help' for a list of commands");
+        self.show_location();
+
+        loop {
+            write!(self.output, "(bffdb) ").ok();
+            self.output.flush().ok();
+
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => return self.final_state(),
+                Ok(_) => {}
+            }
+
+            let mut words = line.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => continue,
+            };
+            let arguments: Vec<&str> = words.collect();
+
+            let stop = match command {
+                "break" | "b" => { self.set_breakpoint(&arguments, true); Stop::Paused }
+                "clear" => { self.set_breakpoint(&arguments, false); Stop::Paused }
+                "breakpoints" | "bl" => { self.list_breakpoints(); Stop::Paused }
+                "step" | "s" => {
+                    let count = arguments.first().and_then(|count| count.parse::<usize>().ok()).unwrap_or(1);
+                    self.step(count)
+                }
+                "next" | "n" => self.next(),
+                "finish" => self.finish(),
+                "continue" | "c" => self.run_until(|_| false),
+                "list" | "l" => {
+                    let count = arguments.first().and_then(|count| count.parse::<usize>().ok()).unwrap_or(5);
+                    self.list(count);
+                    Stop::Paused
+                }
+                "registers" | "r" => { self.print_registers(); Stop::Paused }
+                "memory" | "x" => { self.dump_memory(&arguments); Stop::Paused }
+                "stack" => { self.print_stack(); Stop::Paused }
+                "callstack" | "bt" => { self.print_call_stack(); Stop::Paused }
+                "help" | "h" => { say!(self, "{}", HELP); Stop::Paused }
+                "quit" | "q" => return self.final_state(),
+                unknown => {
+                    say!(self, "unknown command '{}', type 'help' for a list of commands", unknown);
+                    Stop::Paused
+                }
+            };
+
+            if let Stop::Finished = stop {
+                return self.final_state();
+            }
+        }
+    }
+
+    fn final_state(&mut self) -> ExecutionState {
+        if let Some(trap) = self.trap.take() {
+            return trap;
+        }
+        if self.vm.is_finished() {
+            ExecutionState::Halted(self.vm.exit_code.unwrap_or(0))
+        } else {
+            ExecutionState::Running
+        }
+    }
+
+    fn resolve(&self, target: &str) -> Option<usize> {
+        match target.parse::<usize>() {
+            Ok(index) => Some(index),
+            Err(_) => self.labels.get(target).copied(),
+        }
+    }
+
+    // owned so it can be printed while writing to `output`
+    fn label_at(&self, index: usize) -> Option<String> {
+        self.labels.iter()
+            .filter(|(_, address)| **address == index)
+            .map(|(label, _)| label.as_str())
+            .min()
+            .map(str::to_string)
+    }
+
+    fn set_breakpoint(&mut self, arguments: &[&str], enable: bool) {
+        let target = match arguments.first() {
+            Some(target) => *target,
+            None => {
+                say!(self, "expected an instruction index or a label");
+                return;
+            }
+        };
+        let index = match self.resolve(target) {
+            Some(index) => index,
+            None => {
+                say!(self, "unknown label '{}'", target);
+                return;
+            }
+        };
+
+        if enable {
+            self.breakpoints.insert(index);
+            say!(self, "breakpoint set at {}", index);
+        } else if self.breakpoints.remove(&index) {
+            say!(self, "breakpoint cleared at {}", index);
+        } else {
+            say!(self, "no breakpoint at {}", index);
+        }
+    }
+
+    fn list_breakpoints(&mut self) {
+        if self.breakpoints.is_empty() {
+            say!(self, "no breakpoints");
+        }
+        for index in &self.breakpoints {
+            match self.label_at(*index) {
+                Some(label) => say!(self, "  {} <{}>", index, label),
+                None => say!(self, "  {}", index),
+            }
+        }
+    }
+
+    // executes one instruction, returning Finished once the program can't continue
+    fn single_step(&mut self) -> Stop {
+        if self.trap.is_some() || self.vm.is_finished() {
+            return Stop::Finished;
+        }
+        if let Err(trap) = self.vm.step() {
+            match self.debug_info.lookup(trap.program_counter) {
+                Some(location) => say!(self, "{}, at {}", trap, location),
+                None => say!(self, "{}", trap),
+            }
+            self.trap = Some(ExecutionState::Trapped(trap));
+            return Stop::Paused;
+        }
+        if self.vm.is_finished() {
+            say!(self, "program exited with status {}", self.vm.exit_code.unwrap_or(0));
+            return Stop::Finished;
+        }
+        Stop::Paused
+    }
+
+    fn step(&mut self, count: usize) -> Stop {
+        for _ in 0..count {
+            if let Stop::Finished = self.single_step() {
+                return Stop::Finished;
+            }
+            if self.trap.is_some() {
+                return Stop::Paused;
+            }
+        }
+        self.show_location();
+        Stop::Paused
+    }
+
+    fn next(&mut self) -> Stop {
+        match self.vm.instruction_list.get(self.vm.program_counter) {
+            Some(Instruction::Call(_)) => {
+                let depth = self.vm.call_stack.len();
+                self.run_until(move |vm| vm.call_stack.len() <= depth)
+            }
+            _ => self.step(1),
+        }
+    }
+
+    fn finish(&mut self) -> Stop {
+        let depth = self.vm.call_stack.len();
+        if depth == 0 {
+            say!(self, "not inside a call");
+            return Stop::Paused;
+        }
+        self.run_until(move |vm| vm.call_stack.len() < depth)
+    }
+
+    // keeps stepping until `done` holds, a breakpoint is hit, or the program stops
+    fn run_until<F: Fn(&VirtualMachine) -> bool>(&mut self, done: F) -> Stop {
+        loop {
+            if let Stop::Finished = self.single_step() {
+                return Stop::Finished;
+            }
+            if self.trap.is_some() {
+                return Stop::Paused;
+            }
+            if self.breakpoints.contains(&self.vm.program_counter) {
+                say!(self, "breakpoint hit");
+                break;
+            }
+            if done(self.vm) {
+                break;
+            }
+        }
+        self.show_location();
+        Stop::Paused
+    }
+
+    fn show_location(&mut self) {
+        let pc = self.vm.program_counter;
+        match (self.vm.instruction_list.get(pc), self.label_at(pc)) {
+            (Some(instruction), Some(label)) => say!(self, "{:>6} <{}>: {:?}", pc, label, instruction),
+            (Some(instruction), None) => say!(self, "{:>6}: {:?}", pc, instruction),
+            (None, _) => say!(self, "{:>6}: <end of program>", pc),
+        }
+        if let Some(location) = self.debug_info.lookup(pc) {
+            say!(self, "        at {}", location);
+        }
+    }
+
+    fn list(&mut self, count: usize) {
+        let pc = self.vm.program_counter;
+        let start = pc.saturating_sub(count);
+        let end = (pc + count + 1).min(self.vm.instruction_list.len());
+        for index in start..end {
+            if let Some(label) = self.label_at(index) {
+                say!(self, "{}:", label);
+            }
+            let marker = if index == pc { "=>" } else if self.breakpoints.contains(&index) { " *" } else { "  " };
+            say!(self, "{} {:>6}: {:?}", marker, index, self.vm.instruction_list[index]);
+        }
+    }
+
+    fn print_registers(&mut self) {
+        for (index, value) in self.vm.registers.iter().enumerate() {
+            let name = if index == STACK_POINTER as usize { "sp".to_string() } else { format!("reg{}", index) };
+            say!(self, "{:>5} = {:#010x} {:>11} {:>11}", name, value, value, *value as i32);
+        }
+        say!(self, "   pc = {}", self.vm.program_counter);
+    }
+
+    fn dump_memory(&mut self, arguments: &[&str]) {
+        let address = match arguments.first().and_then(|address| parse_number(address)) {
+            Some(address) => address,
+            None => {
+                say!(self, "expected an address");
+                return;
+            }
+        };
+        let length = arguments.get(1).and_then(|length| parse_number(length)).unwrap_or(64);
+
+        let start = address.min(self.vm.memory.len());
+        let end = address.saturating_add(length).min(self.vm.memory.len());
+        for (row, chunk) in self.vm.memory[start..end].chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let text: String = chunk.iter()
+                .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+                .collect();
+            say!(self, "{:08x}: {:<47} |{}|", start + row * 16, hex.join(" "), text);
+        }
+    }
+
+    fn print_stack(&mut self) {
+        let stack_pointer = (self.vm.registers[STACK_POINTER as usize] as usize).min(self.vm.stack.len());
+        if stack_pointer == 0 {
+            say!(self, "stack is empty");
+        }
+        for index in (0..stack_pointer).rev() {
+            let value = self.vm.stack[index];
+            say!(self, "  [{}] {:#010x} {}", index, value, value);
+        }
+    }
+
+    fn print_call_stack(&mut self) {
+        say!(self, "  #0 {}", self.describe(self.vm.program_counter));
+        for (depth, address) in self.vm.call_stack.iter().rev().enumerate() {
+            say!(self, "  #{} {}", depth + 1, self.describe(*address as usize));
+        }
+    }
+
+    fn describe(&self, index: usize) -> String {
+        if let Some(location) = self.debug_info.lookup(index) {
+            return format!("{} at {}", index, location);
+        }
+        let mut owner = None;
+        for (label, address) in &self.labels {
+            if *address <= index && owner.is_none_or(|(_, best)| *address > best) {
+                owner = Some((label.as_str(), *address));
+            }
+        }
+        match owner {
+            Some((label, _)) => format!("{} in {}", index, label),
+            None => format!("{}", index),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse::<usize>().ok(),
+    }
+}
@@ -0,0 +1,13 @@
+pub mod debugger;
+
+use bffcore::constants::types::Bits;
+
+// the process status for a guest exit code. unix keeps only the low 8 bits, so a nonzero code like 256
+// that would read as success exits with 1 instead
+pub fn exit_status(exit_code: Bits) -> i32 {
+    if exit_code != 0 && exit_code & 0xff == 0 {
+        1
+    } else {
+        exit_code as i32
+    }
+}
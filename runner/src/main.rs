@@ -1,23 +1,66 @@
+use std::collections::HashMap;
 use std::collections::vec_deque::VecDeque;
-use bffcore;
-use bffcore::engine::bfo_reader::BFOReader;
+use std::fs::File;
+use std::io::BufWriter;
+use bffcore::engine::bfo_reader::{BFOReader, BfoError};
+use bffcore::engine::trace::Tracer;
+use std::str::FromStr;
+use bffcore::constants::constants::{REGISTER_COUNT, VERSION};
+use bffcore::constants::types::Bits;
+use bffcore::engine::config::VmConfig;
+use bffcore::engine::debug_info::DebugInfo;
+use bffcore::engine::program::BFFProgram;
+use bffcore::engine::section::SymbolKind;
+use bffcore::engine::snapshot::VmSnapshot;
+use bffcore::engine::virtual_machine::ExecutionState;
+use bff::debugger::Debugger;
 
 fn main() {
     let mut args: VecDeque<String> = std::env::args().collect();
     let _path = args.pop_front().unwrap();
     let mut file_path = "./assembly/main.bfo".to_string();
     let mut debug = false;
+    let mut max_steps: Option<u64> = None;
+    let mut trace_path: Option<String> = None;
+    let mut config = VmConfig::new();
+    let mut snapshot_path: Option<String> = None;
+    let mut resume_path: Option<String> = None;
 
-    if args.len() > 0 && !args[0].starts_with("-"){
+    let mut required_key: Option<String> = None;
+
+    match args.front().map(String::as_str) {
+        Some("upgrade") => {
+            args.pop_front();
+            upgrade(args);
+            return;
+        }
+        Some("sign") => {
+            args.pop_front();
+            sign(args);
+            return;
+        }
+        Some("to-json") => {
+            args.pop_front();
+            to_json(args);
+            return;
+        }
+        Some("from-json") => {
+            args.pop_front();
+            from_json(args);
+            return;
+        }
+        _ => {}
+    }
+
+    let file_given = !args.is_empty() && !args[0].starts_with("-");
+    if file_given {
         file_path = args.pop_front().unwrap();
         if !file_path.ends_with(".bfo") {
             eprintln!("Warning: Given File is not a '.bfo' file");
         }
-    } else {
-        eprintln!("Warning: Default File not found, using '{}' instead", file_path);
     }
 
-    while args.len() != 0 {
+    while !args.is_empty() {
         let current_arg = args.pop_front().unwrap();
 
         if current_arg.starts_with("-"){
@@ -25,21 +68,363 @@ fn main() {
                 "--debug" | "-d" => {
                     debug = true;
                 }
-                unknown => {
-                    unimplemented!("Unknown Argument: '{}'", unknown)
+                "--max-steps" => {
+                    max_steps = Some(parse_value(&mut args, "--max-steps", "a number of steps"));
+                }
+                "--trace" => {
+                    trace_path = Some(parse_value(&mut args, "--trace", "a file path"));
+                }
+                "--snapshot-on-exit" => {
+                    snapshot_path = Some(parse_value(&mut args, "--snapshot-on-exit", "a file path"));
+                }
+                "--resume" => {
+                    resume_path = Some(parse_value(&mut args, "--resume", "a snapshot file path"));
+                }
+                "--require-signed" => {
+                    required_key = Some(parse_value(&mut args, "--require-signed", "a hex encoded public key"));
+                }
+                "--memory-size" => {
+                    config.memory_size = parse_value(&mut args, "--memory-size", "a size in bytes");
+                }
+                "--stack-size" => {
+                    config.stack_size = parse_value(&mut args, "--stack-size", "a size in stack words");
                 }
+                "--max-call-depth" => {
+                    config.max_call_depth = parse_value(&mut args, "--max-call-depth", "a number of calls");
+                }
+                "--register" => {
+                    let assignment: String = parse_value(&mut args, "--register", "'regN=value'");
+                    match parse_register(&assignment) {
+                        Some((register, value)) => config.registers[register] = value,
+                        None => fail(&format!("'--register' expects 'regN=value' with N below {}, got '{}'", REGISTER_COUNT, assignment)),
+                    }
+                }
+                unknown => fail(&format!("Unknown Argument: '{}'", unknown)),
+            }
+        } else {
+            fail(&format!("Unknown Argument: '{}'", current_arg));
+        }
+    }
+
+    if debug && max_steps.is_some() {
+        fail("'--max-steps' can't be used with '--debug', step through the program with the debugger instead");
+    }
+
+    let mut vm = config.build();
+    let mut labels = HashMap::new();
+    let mut debug_info = DebugInfo::new();
+    match &resume_path {
+        // the snapshot carries the program, memory and sizes, so the bfo file and size flags aren't used
+        Some(resume_path) => {
+            if required_key.is_some() {
+                fail("'--require-signed' can't be used with '--resume', snapshots aren't signed");
+            }
+            let snapshot = match std::fs::read(resume_path) {
+                Ok(contents) => VmSnapshot::from_bytes(&contents),
+                Err(e) => fail(&format!("Couldn't read snapshot '{}': {}", resume_path, e)),
+            };
+            match snapshot {
+                Ok(snapshot) => vm.restore(snapshot),
+                Err(e) => fail(&format!("Couldn't resume from '{}': {}", resume_path, e)),
+            }
+        }
+        None => {
+            if !file_given {
+                eprintln!("Warning: Default File not found, using '{}' instead", file_path);
+            }
+            let bff_program = match &required_key {
+                Some(public_key) => read_signed(&file_path, public_key),
+                None => BFOReader::read_file_from_path(file_path.clone()),
+            };
+            let bff_program = match bff_program {
+                Ok(bff_program) => bff_program,
+                Err(e) => fail(&format!("Couldn't load '{}': {}", file_path, e)),
+            };
+            if bff_program.object {
+                fail(&format!("'{}' is an object file, link it with bffld first", file_path));
+            }
+            for symbol in &bff_program.symbols {
+                if symbol.kind == SymbolKind::Code {
+                    labels.insert(symbol.name.clone(), symbol.value as usize);
+                }
+            }
+            if let Err(reason) = bff_program.load_into(&mut vm) {
+                fail(&format!("Couldn't load '{}': {}", file_path, reason));
+            }
+            debug_info = bff_program.debug;
+        }
+    }
+
+    if let Some(trace_path) = &trace_path {
+        match File::create(trace_path) {
+            Ok(file) => vm.tracer = Some(Tracer::new(Box::new(BufWriter::new(file)))),
+            Err(e) => {
+                eprintln!("Error: Couldn't create trace file '{}': {}", trace_path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let state = match max_steps {
+        _ if debug => Debugger::new(&mut vm, labels, debug_info.clone()).run(),
+        Some(steps) => vm.execute_for(steps),
+        None => match vm.execute_instruction_list() {
+            Ok(exit_code) => ExecutionState::Halted(exit_code),
+            Err(trap) => ExecutionState::Trapped(trap),
+        }
+    };
+
+    if let Some(tracer) = &mut vm.tracer {
+        if let Err(e) = tracer.flush() {
+            eprintln!("Error: Couldn't write trace file: {}", e);
+        }
+    }
+
+    if let Some(snapshot_path) = &snapshot_path {
+        if let Err(e) = std::fs::write(snapshot_path, vm.snapshot().to_bytes()) {
+            eprintln!("Error: Couldn't write snapshot '{}': {}", snapshot_path, e);
+        }
+    }
+
+    match state {
+        ExecutionState::Halted(exit_code) => {
+            std::process::exit(bff::exit_status(exit_code));
+        }
+        ExecutionState::Trapped(trap) => {
+            // the debugger already showed it
+            if !debug {
+                match debug_info.lookup(trap.program_counter) {
+                    Some(location) => eprintln!("Error: {}, at {}", trap, location),
+                    None => eprintln!("Error: {}", trap),
+                }
+            }
+            std::process::exit(1);
+        }
+        ExecutionState::Running if debug => {
+            // the debugger was quit before the program finished
+            std::process::exit(0);
+        }
+        ExecutionState::Running => {
+            match debug_info.lookup(vm.program_counter) {
+                Some(location) => eprintln!("Error: step limit of {} exceeded at pc={}, at {}", max_steps.unwrap_or(0), vm.program_counter, location),
+                None => eprintln!("Error: step limit of {} exceeded at pc={}", max_steps.unwrap_or(0), vm.program_counter),
             }
+            std::process::exit(1);
         }
     }
+}
 
-    let contents = match std::fs::read(file_path.clone()){
-        Ok(contents) => contents,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            panic!("Couldnt read {}", file_path)
+// bff upgrade <file.bfo> [-o <out.bfo>] [--compact], rewrites the file in place unless given an output
+fn upgrade(mut args: VecDeque<String>) {
+    let file_path = match args.pop_front() {
+        Some(file_path) if !file_path.starts_with("-") => file_path,
+        _ => fail("Usage: bff upgrade <file.bfo> [-o <out.bfo>] [--compact]"),
+    };
+    let mut output_path = file_path.clone();
+    let mut compact = false;
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => output_path = parse_value(&mut args, &current_arg, "a file path"),
+            "--compact" => compact = true,
+            unknown => fail(&format!("Unknown Argument: '{}'", unknown)),
         }
+    }
+
+    let mut bff_program = match BFOReader::read_file_from_path(file_path.clone()) {
+        Ok(bff_program) => bff_program,
+        Err(e) => fail(&format!("Couldn't load '{}': {}", file_path, e)),
+    };
+    let old_version = bff_program.version;
+    // compact files stay compact
+    let compact = compact || bff_program.compact;
+    if old_version == VERSION && output_path == file_path && bff_program.compact == compact {
+        println!("'{}' is already version {}.{}.{}", file_path, VERSION.0, VERSION.1, VERSION.2);
+        return;
+    }
+
+    let old_size = bff_program.to_bfo_bytes().len();
+    bff_program.upgrade();
+    bff_program.compact = compact;
+    let bytes = bff_program.to_bfo_bytes();
+    if let Err(e) = std::fs::write(&output_path, &bytes) {
+        fail(&format!("Couldn't write '{}': {}", output_path, e));
+    }
+    println!(
+        "Upgraded '{}' from {}.{}.{} to {}.{}.{}{}, {} -> {} bytes",
+        file_path, old_version.0, old_version.1, old_version.2, VERSION.0, VERSION.1, VERSION.2,
+        if compact { " compact" } else { "" }, old_size, bytes.len()
+    );
+}
+
+// bff sign <file.bfo> --key <secret key file> [-o <out.bfo>], the key file holds 32 hex encoded bytes
+#[cfg(feature = "signing")]
+fn sign(mut args: VecDeque<String>) {
+    let usage = "Usage: bff sign <file.bfo> --key <secret key file> [-o <out.bfo>]";
+    let file_path = match args.pop_front() {
+        Some(file_path) if !file_path.starts_with("-") => file_path,
+        _ => fail(usage),
+    };
+    let mut output_path = file_path.clone();
+    let mut key_path: Option<String> = None;
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => output_path = parse_value(&mut args, &current_arg, "a file path"),
+            "--key" => key_path = Some(parse_value(&mut args, "--key", "a secret key file")),
+            unknown => fail(&format!("Unknown Argument: '{}'", unknown)),
+        }
+    }
+    let key_path = key_path.unwrap_or_else(|| fail(usage));
+
+    let secret_key = match std::fs::read_to_string(&key_path) {
+        Ok(contents) => parse_hex_key(contents.trim())
+            .unwrap_or_else(|| fail(&format!("'{}' should hold 64 hex digits", key_path))),
+        Err(e) => fail(&format!("Couldn't read key '{}': {}", key_path, e)),
     };
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&secret_key);
 
-    let mut bff_program = BFOReader::read_program(contents);
-    bff_program.execute();
-}
\ No newline at end of file
+    let bff_program = match BFOReader::read_file_from_path(file_path.clone()) {
+        Ok(bff_program) => bff_program,
+        Err(e) => fail(&format!("Couldn't load '{}': {}", file_path, e)),
+    };
+    let signed = match bff_program.to_signed_bfo_bytes(&signing_key) {
+        Some(signed) => signed,
+        None => fail(&format!("'{}' is too old to be signed, run 'bff upgrade' first", file_path)),
+    };
+    if let Err(e) = std::fs::write(&output_path, signed) {
+        fail(&format!("Couldn't write '{}': {}", output_path, e));
+    }
+    let public_key: String = signing_key.verifying_key().as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    println!("Signed '{}', public key {}", output_path, public_key);
+}
+
+#[cfg(not(feature = "signing"))]
+fn sign(_args: VecDeque<String>) {
+    fail("bff was built without the 'signing' feature");
+}
+
+// bff to-json <file.bfo> [-o <out.json>], prints the json unless given an output
+#[cfg(feature = "json")]
+fn to_json(mut args: VecDeque<String>) {
+    let file_path = match args.pop_front() {
+        Some(file_path) if !file_path.starts_with("-") => file_path,
+        _ => fail("Usage: bff to-json <file.bfo> [-o <out.json>]"),
+    };
+    let mut output_path: Option<String> = None;
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => output_path = Some(parse_value(&mut args, &current_arg, "a file path")),
+            unknown => fail(&format!("Unknown Argument: '{}'", unknown)),
+        }
+    }
+
+    let bff_program = match BFOReader::read_file_from_path(file_path.clone()) {
+        Ok(bff_program) => bff_program,
+        Err(e) => fail(&format!("Couldn't load '{}': {}", file_path, e)),
+    };
+    let json = bff_program.to_json();
+    match output_path {
+        Some(output_path) => {
+            if let Err(e) = std::fs::write(&output_path, json) {
+                fail(&format!("Couldn't write '{}': {}", output_path, e));
+            }
+        }
+        None => print!("{}", json),
+    }
+}
+
+// bff from-json <file.json> [-o <out.bfo>], writes next to the json file unless given an output
+#[cfg(feature = "json")]
+fn from_json(mut args: VecDeque<String>) {
+    let file_path = match args.pop_front() {
+        Some(file_path) if !file_path.starts_with("-") => file_path,
+        _ => fail("Usage: bff from-json <file.json> [-o <out.bfo>]"),
+    };
+    let mut output_path = std::path::Path::new(&file_path).with_extension("bfo").to_string_lossy().to_string();
+    while let Some(current_arg) = args.pop_front() {
+        match &*current_arg {
+            "-o" | "--output" => output_path = parse_value(&mut args, &current_arg, "a file path"),
+            unknown => fail(&format!("Unknown Argument: '{}'", unknown)),
+        }
+    }
+
+    let json = match std::fs::read_to_string(&file_path) {
+        Ok(json) => json,
+        Err(e) => fail(&format!("Couldn't read '{}': {}", file_path, e)),
+    };
+    let bff_program = match BFFProgram::from_json(&json) {
+        Ok(bff_program) => bff_program,
+        Err(e) => fail(&format!("Couldn't parse '{}': {}", file_path, e)),
+    };
+    if bff_program.version > VERSION {
+        fail(&format!("'{}' has version {:?}, bff only writes up to {:?}", file_path, bff_program.version, VERSION));
+    }
+    if let Err(e) = std::fs::write(&output_path, bff_program.to_bfo_bytes()) {
+        fail(&format!("Couldn't write '{}': {}", output_path, e));
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn to_json(_args: VecDeque<String>) {
+    fail("bff was built without the 'json' feature");
+}
+
+#[cfg(not(feature = "json"))]
+fn from_json(_args: VecDeque<String>) {
+    fail("bff was built without the 'json' feature");
+}
+
+#[cfg(feature = "signing")]
+fn read_signed(file_path: &str, public_key: &str) -> Result<BFFProgram, BfoError> {
+    let public_key = parse_hex_key(public_key)
+        .and_then(|key| ed25519_dalek::VerifyingKey::from_bytes(&key).ok())
+        .unwrap_or_else(|| fail(&format!("'--require-signed' expects a 64 hex digit public key, got '{}'", public_key)));
+    let program = std::fs::read(file_path)
+        .map_err(|err| BfoError::Io(format!("couldn't read '{}': {}", file_path, err)))?;
+
+    BFOReader::read_signed_program(program, &public_key)
+}
+
+#[cfg(not(feature = "signing"))]
+fn read_signed(_file_path: &str, _public_key: &str) -> Result<BFFProgram, BfoError> {
+    fail("'--require-signed' needs bff to be built with the 'signing' feature");
+}
+
+#[cfg(feature = "signing")]
+fn parse_hex_key(text: &str) -> Option<[u8; 32]> {
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    let mut key = [0; 32];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
+
+// pops the value following `flag`, exiting with an error if it's missing or doesn't parse
+fn parse_value<T: FromStr>(args: &mut VecDeque<String>, flag: &str, expected: &str) -> T {
+    match args.pop_front().map(|value| value.parse::<T>()) {
+        Some(Ok(value)) => value,
+        _ => fail(&format!("'{}' expects {}", flag, expected)),
+    }
+}
+
+// "reg3=42", signed values are stored as their two's complement
+fn parse_register(assignment: &str) -> Option<(usize, Bits)> {
+    let (register, value) = assignment.split_once('=')?;
+    let register = register.trim().strip_prefix("reg")?.parse::<usize>().ok()?;
+    if register >= REGISTER_COUNT {
+        return None;
+    }
+    let value = value.trim();
+    let value = match value.parse::<Bits>() {
+        Ok(value) => value,
+        Err(_) => value.parse::<i32>().ok()? as Bits,
+    };
+    Some((register, value))
+}
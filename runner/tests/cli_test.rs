@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use bffcore::constants::constants::VERSION;
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::program::BFFProgram;
+
+// a bfo file of its own for every test, so they can run in parallel
+fn write_program(name: &str, instructions: Vec<Instruction>) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("bff_cli_test_{}_{}.bfo", std::process::id(), name));
+    std::fs::write(&path, BFFProgram::new(VERSION, instructions, vec![]).to_bfo_bytes()).unwrap();
+    path
+}
+
+// runs bff on the program with `args`, returning the process status and stderr
+fn run(name: &str, instructions: Vec<Instruction>, args: &[&str]) -> (Option<i32>, String) {
+    let path = write_program(name, instructions);
+    let output = Command::new(env!("CARGO_BIN_EXE_bff")).arg(&path).args(args).output().unwrap();
+    std::fs::remove_file(&path).ok();
+    (output.status.code(), String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+#[test]
+fn exit_codes_that_look_like_success_fail() {
+    assert_eq!(bff::exit_status(0), 0);
+    assert_eq!(bff::exit_status(7), 7);
+    assert_eq!(bff::exit_status(256), 1);
+    assert_eq!(bff::exit_status(0x1_0000), 1);
+
+    assert_eq!(run("exit_256", vec![Instruction::ExitImmediate(256)], &[]).0, Some(1));
+    assert_eq!(run("exit_7", vec![Instruction::ExitImmediate(7)], &[]).0, Some(7));
+}
+
+#[test]
+fn unknown_arguments_fail() {
+    let (status, stderr) = run("stray", vec![Instruction::Halt], &["stray-arg"]);
+    assert_eq!(status, Some(1));
+    assert!(stderr.contains("Unknown Argument: 'stray-arg'"), "{}", stderr);
+
+    let (status, stderr) = run("unknown_flag", vec![Instruction::Halt], &["--bogus"]);
+    assert_eq!(status, Some(1));
+    assert!(stderr.contains("Unknown Argument: '--bogus'"), "{}", stderr);
+}
+
+#[test]
+fn max_steps_is_rejected_with_debug() {
+    let (status, stderr) = run("debug_steps", vec![Instruction::Halt], &["--debug", "--max-steps", "5"]);
+    assert_eq!(status, Some(1));
+    assert!(stderr.contains("'--max-steps' can't be used with '--debug'"), "{}", stderr);
+}
+
+#[test]
+fn debugger_traps_are_reported_once() {
+    let path = write_program("debug_trap", vec![Instruction::Return]);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bff"))
+        .arg(&path)
+        .arg("--debug")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"continue\nquit\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout.matches("call stack underflow").count(), 1, "{}", stdout);
+    assert!(!stderr.contains("call stack underflow"), "{}", stderr);
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use bff::debugger::Debugger;
+use bffcore::constants::instructions::Instruction;
+use bffcore::engine::debug_info::{DebugInfo, SourceLocation};
+use bffcore::engine::trap::TrapReason;
+use bffcore::engine::virtual_machine::{ExecutionState, VirtualMachine};
+
+// main calls outer, which calls inner, every call adds to reg1 so the exit status shows what ran
+fn program() -> (VirtualMachine, HashMap<String, usize>) {
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::MoveImmediate(1, 1),
+        Instruction::Call(5),
+        Instruction::AddImmediate(1, 1, 100),
+        Instruction::Exit(1),
+        Instruction::Call(8), // outer
+        Instruction::AddImmediate(1, 1, 10),
+        Instruction::Return,
+        Instruction::AddImmediate(1, 1, 1), // inner
+        Instruction::Return,
+    ]);
+    let labels = HashMap::from([("outer".to_string(), 5), ("inner".to_string(), 8)]);
+    (vm, labels)
+}
+
+// runs `commands` and returns the final state with everything the debugger printed
+fn debug(vm: &mut VirtualMachine, labels: HashMap<String, usize>, debug_info: DebugInfo, commands: &str) -> (ExecutionState, String) {
+    let mut debugger = Debugger::with_output(vm, labels, debug_info, vec![]);
+    let state = debugger.run_with(commands.as_bytes());
+    (state, String::from_utf8(debugger.output).unwrap())
+}
+
+#[test]
+fn breakpoints_resolve_labels() {
+    let (mut vm, labels) = program();
+    let (state, output) = debug(&mut vm, labels, DebugInfo::new(), "break inner\nb 3\nbreak nowhere\nbl\ncontinue\nclear 3\nclear 3\nquit\n");
+    assert_eq!(state, ExecutionState::Running);
+    assert_eq!((vm.program_counter, vm.call_stack.len()), (8, 2));
+    for line in [
+        "breakpoint set at 8",
+        "unknown label 'nowhere'",
+        "  3\n  8 <inner>",
+        "breakpoint hit\n     8 <inner>: AddImmediate(1, 1, 1)",
+        "breakpoint cleared at 3",
+        "no breakpoint at 3",
+    ] {
+        assert!(output.contains(line), "'{}' not in\n{}", line, output);
+    }
+}
+
+#[test]
+fn step_and_next() {
+    let (mut vm, labels) = program();
+    let (_, output) = debug(&mut vm, labels.clone(), DebugInfo::new(), "step 2\nnext\nquit\n");
+    // next runs the whole call to outer, and inner with it
+    assert_eq!((vm.program_counter, vm.call_stack.len(), vm.registers[1]), (3, 0, 12));
+    assert!(output.contains("     2: Call(5)\n"), "{}", output);
+
+    // inside outer, next only steps over the call to inner and stays at the same depth
+    let (mut vm, _) = program();
+    debug(&mut vm, labels.clone(), DebugInfo::new(), "s 3\nn\nq\n");
+    assert_eq!((vm.program_counter, vm.call_stack.len(), vm.registers[1]), (6, 1, 2));
+
+    // a breakpoint inside the call still stops next
+    let (mut vm, _) = program();
+    debug(&mut vm, labels, DebugInfo::new(), "break inner\nstep 2\nnext\nquit\n");
+    assert_eq!((vm.program_counter, vm.call_stack.len()), (8, 2));
+}
+
+#[test]
+fn finish_returns_from_the_current_call() {
+    let (mut vm, labels) = program();
+    debug(&mut vm, labels.clone(), DebugInfo::new(), "break inner\ncontinue\nfinish\nquit\n");
+    assert_eq!((vm.program_counter, vm.call_stack.len(), vm.registers[1]), (6, 1, 2));
+
+    let (mut vm, _) = program();
+    let (_, output) = debug(&mut vm, labels, DebugInfo::new(), "break inner\ncontinue\nfinish\nfinish\nfinish\nquit\n");
+    assert_eq!((vm.program_counter, vm.call_stack.len(), vm.registers[1]), (3, 0, 12));
+    assert!(output.contains("not inside a call"), "{}", output);
+}
+
+#[test]
+fn continue_stops_on_exit_and_traps() {
+    let (mut vm, labels) = program();
+    let (state, output) = debug(&mut vm, labels, DebugInfo::new(), "continue\n");
+    assert_eq!(state, ExecutionState::Halted(112));
+    assert!(output.contains("program exited with status 112"), "{}", output);
+
+    // a return outside of any call, the trap is reported with its source location
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![Instruction::MoveImmediate(1, 1), Instruction::Return, Instruction::Halt]);
+    let mut debug_info = DebugInfo::new();
+    debug_info.insert(2, SourceLocation::new("main.bffasm", 3, 1, None));
+    let (state, output) = debug(&mut vm, HashMap::new(), debug_info, "continue\nregisters\ncontinue\n");
+    match state {
+        ExecutionState::Trapped(trap) => assert_eq!((trap.program_counter, trap.reason), (2, TrapReason::CallStackUnderflow)),
+        state => panic!("expected a trap, got {:?}", state),
+    }
+    assert!(output.contains("call stack underflow"), "{}", output);
+    assert!(output.contains(", at main.bffasm:3\n"), "{}", output);
+    assert!(output.contains("   pc = 2\n"), "{}", output);
+}